@@ -0,0 +1,93 @@
+//! Per-frame cost of rendering a board with dozens of annotated units.
+//! Run with `cargo bench`; fails if the 99th percentile frame takes longer than the 30 ms input tick.
+//! The slowest frame is shown but not checked, a single one being at the mercy of the scheduler.
+
+use std::time::{Duration, Instant};
+
+use shulk::graphics;
+use shulk::rendering::{rendering, theme};
+
+const BOARD_W: u32 = 80;
+const BOARD_H: u32 = 40;
+const UNITS: u32 = 48;
+const FRAMES: u32 = 300;
+/// Frames rendered before timing, filling caches and letting the layout settle
+const WARM_UP: u32 = 20;
+const FRAME_BUDGET: Duration = Duration::from_millis(30);
+
+/// Xorshift generator, for the same moves at each run
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn below(&mut self, n: u32) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % n as u64) as u32
+    }
+}
+
+fn get_visuals(units: &[(u32, u32)]) -> Vec<graphics::Visual> {
+    let mut visuals = vec![];
+    for y in 0..BOARD_H {
+        for x in 0..BOARD_W {
+            visuals.push(graphics::Visual{content:1, x, y, id:None, color:graphics::Color::Default, layer:graphics::Layer::Floor});
+        }
+    }
+    for (id, (x, y)) in units.iter().enumerate() {
+        visuals.push(graphics::Visual{content:4, x:*x, y:*y, id:Some(id as u32), color:graphics::Color::Blue, layer:graphics::Layer::Units});
+    }
+    visuals
+}
+
+/// Frame durations, each unit moving a square every few frames unless `still`
+fn run(keep_state: bool, still: bool) -> Vec<Duration> {
+    let mut rng = Rng{state:0x2545_f491_4f6c_dd1d};
+    let mut units: Vec<(u32, u32)> = (0..UNITS).map(|_| (rng.below(BOARD_W), rng.below(BOARD_H))).collect();
+    let texts: Vec<graphics::Text> = (0..UNITS).map(|id| graphics::Text::ItemAnnotation(id, format!("Brother {}", id))).collect();
+    let theme = theme::Theme::unicode();
+    let mut state = rendering::BoardState::new();
+
+    let mut durations = vec![];
+    for frame in 0..WARM_UP + FRAMES {
+        for unit in units.iter_mut() {
+            if !still && rng.below(4) == 0 {
+                unit.0 = (unit.0 + BOARD_W + rng.below(3) - 1) % BOARD_W;
+                unit.1 = (unit.1 + BOARD_H + rng.below(3) - 1) % BOARD_H;
+            }
+        }
+        let visuals = get_visuals(&units);
+        if !keep_state {
+            state = rendering::BoardState::new();
+        }
+        let start = Instant::now();
+        rendering::render(&visuals, &texts, &theme, &mut state, 200, 60, Some(0)).expect("terminal too small");
+        if frame >= WARM_UP {
+            durations.push(start.elapsed());
+        }
+    }
+    durations
+}
+
+/// Print the distribution of `durations`, returning its 99th percentile
+fn report(name: &str, mut durations: Vec<Duration>) -> Duration {
+    durations.sort();
+    let total: Duration = durations.iter().sum();
+    let p99 = durations[durations.len() * 99 / 100];
+    println!("{:<12} mean {:>9.3?}  median {:>9.3?}  p99 {:>9.3?}  max {:>9.3?}",
+        name, total / durations.len() as u32, durations[durations.len() / 2], p99, durations[durations.len() - 1]);
+    p99
+}
+
+fn main() {
+    println!("{} units on a {}x{} board, {} frames after {} to warm up", UNITS, BOARD_W, BOARD_H, FRAMES, WARM_UP);
+    report("no state", run(false, false));
+    report("units still", run(true, true));
+    let p99 = report("kept state", run(true, false));
+    if p99 > FRAME_BUDGET {
+        eprintln!("99th percentile frame {:?} over the {:?} budget", p99, FRAME_BUDGET);
+        std::process::exit(1);
+    }
+}
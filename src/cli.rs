@@ -0,0 +1,183 @@
+
+use log::LevelFilter;
+use std::path::{Path, PathBuf};
+
+use shulk::keymap;
+use shulk::rendering::theme;
+
+pub const USAGE: &str = "Usage: shulk [OPTIONS]
+
+Options:
+    --mission FILE      Play the mission described in a JSON file
+    --load FILE         Resume a saved game
+    --seed N            Seed the dice of a new game
+    --log-file FILE     Write logs to FILE [default: log/output.log]
+    --log-level LEVEL   off, error, warn, info, debug or trace [default: info]
+    --ai NAME           Opponent: none [default: none]
+    --keymap FILE       Load keybindings from FILE [default: keymap.json]
+    --theme NAME|FILE   unicode, ascii or a JSON theme file [default: theme.json if present, else unicode
+                        or ascii as the locale and TERM allow]
+    --double-width      Draw each tile on two columns
+    --headless FILE     Play the inputs of a script file without a terminal
+    --replay FILE       Watch a recorded game
+    --help              Print this help";
+
+/// Who plays the genestealers
+pub enum Opponent {
+    /// Nobody yet: there is no AI to choose from
+    None,
+}
+
+impl Opponent {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Opponent::None => "none",
+        }
+    }
+}
+
+pub enum Theme {
+    /// theme.json if present, the unicode or ascii theme as the terminal allows otherwise
+    Default,
+    Named(String),
+    File(PathBuf),
+}
+
+impl Theme {
+    pub fn load(&self) -> Result<theme::Theme, String> {
+        match self {
+            Theme::Default => theme::Theme::load_or_default(Path::new(theme::DEFAULT_THEME_PATH)),
+            Theme::Named(name) => theme::Theme::named(name),
+            Theme::File(path) => theme::Theme::load(path),
+        }
+    }
+}
+
+pub enum Mode {
+    Play,
+    Headless(PathBuf),
+    Replay(PathBuf),
+}
+
+pub struct Options {
+    pub mission: Option<PathBuf>,
+    pub load: Option<PathBuf>,
+    pub seed: Option<u64>,
+    pub log_file: PathBuf,
+    pub log_level: LevelFilter,
+    pub opponent: Opponent,
+    pub keymap: PathBuf,
+    pub theme: Theme,
+    pub double_width: bool,
+    pub mode: Mode,
+    pub help: bool,
+}
+
+impl Options {
+    /// Parse the arguments following the program name
+    pub fn parse<I>(args: I) -> Result<Options, String>
+    where I: IntoIterator<Item = String>
+    {
+        let mut options = Options{
+            mission: None,
+            load: None,
+            seed: None,
+            log_file: PathBuf::from("log/output.log"),
+            log_level: LevelFilter::Info,
+            opponent: Opponent::None,
+            keymap: PathBuf::from(keymap::DEFAULT_KEYMAP_PATH),
+            theme: Theme::Default,
+            double_width: false,
+            mode: Mode::Play,
+            help: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("{} expects a value", arg));
+            match arg.as_str() {
+                "--help" | "-h" => options.help = true,
+                "--mission" => options.mission = Some(PathBuf::from(value()?)),
+                "--load" => options.load = Some(PathBuf::from(value()?)),
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = Some(seed.parse().map_err(|_| format!("invalid seed '{}'", seed))?);
+                }
+                "--log-file" => options.log_file = PathBuf::from(value()?),
+                "--log-level" => {
+                    let level = value()?;
+                    options.log_level = level.parse().map_err(|_| format!("invalid log level '{}'", level))?;
+                }
+                "--ai" => options.opponent = match value()?.as_str() {
+                    "none" => Opponent::None,
+                    ai => return Err(format!("unknown AI '{}', available: none", ai)),
+                },
+                "--keymap" => options.keymap = PathBuf::from(value()?),
+                "--theme" => options.theme = match value()?.as_str() {
+                    name @ ("unicode" | "ascii") => Theme::Named(name.to_string()),
+                    path => Theme::File(PathBuf::from(path)),
+                },
+                "--double-width" => options.double_width = true,
+                "--headless" => options.mode = set_mode(&options.mode, Mode::Headless(PathBuf::from(value()?)))?,
+                "--replay" => options.mode = set_mode(&options.mode, Mode::Replay(PathBuf::from(value()?)))?,
+                _ => return Err(format!("unknown option '{}'", arg)),
+            }
+        }
+
+        let new_game = options.mission.is_some() || options.seed.is_some();
+        if options.load.is_some() && new_game {
+            return Err("--load cannot be combined with --mission or --seed".to_string());
+        }
+        if let Mode::Replay(_) = options.mode {
+            if options.load.is_some() || new_game {
+                return Err("--replay cannot be combined with --load, --mission or --seed".to_string());
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn set_mode(current: &Mode, mode: Mode) -> Result<Mode, String> {
+    match current {
+        Mode::Play => Ok(mode),
+        _ => Err("--headless and --replay cannot be combined".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn picks_the_mode() {
+        assert!(matches!(parse(&[]).unwrap().mode, Mode::Play));
+        let options = parse(&["--headless", "moves.txt", "--seed", "7"]).unwrap();
+        assert!(matches!(&options.mode, Mode::Headless(path) if path == Path::new("moves.txt")));
+        assert_eq!(options.seed, Some(7));
+        assert!(matches!(parse(&["--replay", "game.replay"]).unwrap().mode, Mode::Replay(_)));
+        assert!(matches!(parse(&["--theme", "ascii"]).unwrap().theme, Theme::Named(_)));
+        assert!(matches!(parse(&["--theme", "dark.json"]).unwrap().theme, Theme::File(_)));
+    }
+
+    #[test]
+    fn rejects_conflicting_flags() {
+        assert!(parse(&["--headless", "a", "--replay", "b"]).is_err());
+        assert!(parse(&["--load", "shulk.sav", "--mission", "m.json"]).is_err());
+        assert!(parse(&["--load", "shulk.sav", "--seed", "3"]).is_err());
+        assert!(parse(&["--replay", "r", "--load", "shulk.sav"]).is_err());
+        assert!(parse(&["--replay", "r", "--mission", "m.json"]).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_values() {
+        assert_eq!(parse(&["--mission"]).err(), Some("--mission expects a value".to_string()));
+        assert_eq!(parse(&["--seed", "x"]).err(), Some("invalid seed 'x'".to_string()));
+        assert!(parse(&["--log-level", "loud"]).is_err());
+        assert!(parse(&["--ai", "tyranid"]).is_err());
+        assert!(parse(&["--fast"]).is_err());
+    }
+}
@@ -1,4 +1,159 @@
+use crossterm::event::{self, Event as CEvent, KeyEvent};
+use log::error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
 pub enum Event<I> {
     Input(I),
     Tick,
+    /// New terminal width and height
+    Resize(u16, u16),
+}
+
+/// Thread reading terminal keys and resizes, sending them with regular ticks
+pub struct InputPump {
+    stopped: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    /// Set by the thread once it saw the pause, so no longer reads the terminal
+    idle: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl InputPump {
+    /// The thread also ends by itself once the receiver is dropped
+    pub fn start(events_tx: mpsc::Sender<Event<KeyEvent>>, tick_rate: Duration) -> InputPump {
+        InputPump::start_with(events_tx, tick_rate, event::poll, event::read)
+    }
+
+    /// Pump reading events with `poll` and `read` instead of from the terminal
+    fn start_with<P, R>(events_tx: mpsc::Sender<Event<KeyEvent>>, tick_rate: Duration, poll: P, read: R) -> InputPump
+    where P: Fn(Duration) -> crossterm::Result<bool> + Send + 'static,
+          R: Fn() -> crossterm::Result<CEvent> + Send + 'static
+    {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        let idle = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stopped = stopped.clone();
+            let paused = paused.clone();
+            let idle = idle.clone();
+            thread::spawn(move || {
+                let mut last_tick = Instant::now();
+                while !stopped.load(Ordering::SeqCst) {
+                    if paused.load(Ordering::SeqCst) {
+                        idle.store(true, Ordering::SeqCst);
+                        thread::sleep(tick_rate);
+                        last_tick = Instant::now();
+                        continue;
+                    }
+                    let timeout = tick_rate
+                        .checked_sub(last_tick.elapsed())
+                        .unwrap_or_else(|| Duration::from_secs(0));
+
+                    match poll(timeout) {
+                        Ok(true) => match read() {
+                            Ok(CEvent::Key(key)) => {
+                                if events_tx.send(Event::Input(key)).is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(CEvent::Resize(w, h)) => {
+                                if events_tx.send(Event::Resize(w, h)).is_err() {
+                                    break;
+                                }
+                            }
+                            Ok(_) => {}
+                            Err(e) => {
+                                error!("Cannot read terminal events: {}", e);
+                                break;
+                            }
+                        },
+                        Ok(false) => {}
+                        Err(e) => {
+                            error!("Cannot poll terminal events: {}", e);
+                            break;
+                        }
+                    }
+
+                    if last_tick.elapsed() >= tick_rate {
+                        if events_tx.send(Event::Tick).is_err() {
+                            break;
+                        }
+                        last_tick = Instant::now();
+                    }
+                }
+            })
+        };
+        InputPump{stopped, paused, idle, thread:Some(thread)}
+    }
+
+    /// Stop reading the terminal and sending ticks, e.g. while a sub-process owns the terminal.
+    /// Returns once the thread no longer polls.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        while !self.idle.load(Ordering::SeqCst) && self.thread.as_ref().is_some_and(|t| !t.is_finished()) {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    pub fn resume(&self) {
+        self.idle.store(false, Ordering::SeqCst);
+        self.paused.store(false, Ordering::SeqCst);
+    }
+
+    /// Wait for the thread to end, within a tick
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for InputPump {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyCode;
+
+    /// A pump with a key always waiting
+    fn busy_pump(tick_rate: Duration) -> (InputPump, mpsc::Receiver<Event<KeyEvent>>) {
+        let (tx, rx) = mpsc::channel();
+        let key = || Ok(CEvent::Key(KeyEvent::from(KeyCode::Char('a'))));
+        (InputPump::start_with(tx, tick_rate, |_| Ok(true), key), rx)
+    }
+
+    #[test]
+    fn paused_pump_sends_nothing() {
+        let tick_rate = Duration::from_millis(2);
+        let (pump, rx) = busy_pump(tick_rate);
+        assert!(matches!(rx.recv_timeout(tick_rate * 50), Ok(Event::Input(_))));
+        pump.pause();
+        while rx.try_recv().is_ok() {}
+        thread::sleep(tick_rate * 20);
+        assert!(rx.try_recv().is_err());
+
+        pump.resume();
+        assert!(matches!(rx.recv_timeout(tick_rate * 50), Ok(Event::Input(_))));
+        pump.stop();
+    }
+
+    #[test]
+    fn pump_ends_with_its_receiver() {
+        let (pump, rx) = busy_pump(Duration::from_millis(2));
+        drop(rx);
+        pump.stop();
+    }
 }
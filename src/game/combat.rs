@@ -0,0 +1,218 @@
+use super::world;
+use super::position;
+use super::log_entry::LogEntry;
+
+/// Squares straight ahead of `pos`, nearest first, up to a wall or the first model
+pub fn get_line_of_sight(world: &world::World, pos: &position::Position) -> Vec<(u32, u32)> {
+    let (dx, dy) = pos.dir.move_frontward();
+    let (mut x, mut y) = (pos.x as i32, pos.y as i32);
+    let mut squares = vec![];
+    loop {
+        x += dx;
+        y += dy;
+        if x < 0 || y < 0 || !world.board.is_accessible(x as u32, y as u32) {
+            break;
+        }
+        squares.push((x as u32, y as u32));
+        if world.is_occupied(x as u32, y as u32) {
+            break;
+        }
+    }
+    squares
+}
+
+/// The genestealer a model at `pos` would shoot at
+pub fn get_target(world: &world::World, pos: &position::Position) -> Option<(u32, u32)> {
+    get_line_of_sight(world, pos).last()
+        .filter(|(x, y)| world.stealers.iter().any(|s| s.x == *x && s.y == *y))
+        .cloned()
+}
+
+/// Terminator `id` fires at its target: two dice, killing on any 6, jamming on doubles while on overwatch
+pub fn shoot(world: &mut world::World, id: u32) -> Vec<LogEntry> {
+    let mut entries = vec![];
+    let (unit, overwatch, target) = match world.get_terminator(id) {
+        Some(t) => (t.name.clone(), t.overwatch, get_target(world, &t.pos)),
+        None => return entries,
+    };
+    let (x, y) = match target {
+        Some(target) => target,
+        None => return entries,
+    };
+    let rolls = vec![world.dice.roll(), world.dice.roll()];
+    entries.push(LogEntry::Shot{unit:unit.clone(), x, y, rolls:rolls.clone()});
+    if rolls.contains(&6) {
+        world.stealers.retain(|s| s.x != x || s.y != y);
+        entries.push(LogEntry::Killed{x, y});
+    }
+    else {
+        entries.push(LogEntry::Missed{x, y});
+    }
+    if let Some(t) = world.get_terminator_mut(id) {
+        t.aps -= 1;
+        if overwatch && rolls[0] == rolls[1] {
+            t.jammed = true;
+            entries.push(LogEntry::Jammed{unit});
+        }
+    }
+    entries
+}
+
+/// Turn blips seen by any terminator into genestealers, spread around the blip square
+pub fn reveal_blips(world: &mut world::World) -> Vec<LogEntry> {
+    let mut entries = vec![];
+    loop {
+        let seen = world.terminators.iter().find_map(|t| {
+            let (x, y) = *get_line_of_sight(world, &t.pos).last()?;
+            let blip = world.blips.iter().position(|b| b.x == x && b.y == y)?;
+            Some((t.name.clone(), blip))
+        });
+        let (unit, index) = match seen {
+            Some(seen) => seen,
+            None => break,
+        };
+
+        let blip = world.blips.remove(index);
+        let mut squares = vec![(blip.x as i32, blip.y as i32)];
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)].iter() {
+            squares.push((blip.x as i32 + dx, blip.y as i32 + dy));
+        }
+        let mut placed = 0;
+        for (x, y) in squares.into_iter() {
+            if placed == blip.stealers {
+                break;
+            }
+            if x >= 0 && y >= 0 && world.board.is_accessible(x as u32, y as u32) && !world.is_occupied(x as u32, y as u32) {
+                world.stealers.push(world::Stealer{x:x as u32, y:y as u32});
+                placed += 1;
+            }
+        }
+        entries.push(LogEntry::BlipRevealed{unit, x:blip.x, y:blip.y, stealers:placed});
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::commands::{Command, CommandLog, Rotation};
+    use crate::game::dice;
+    use crate::game::position::Direction;
+
+    /// A terminator facing a genestealer three squares away, rolling dice from `seed`
+    fn duel(seed: u64) -> world::World {
+        let mut world = world::World::for_test(&["EEEEE"], &[(0, 0, Direction::Right)]);
+        world.stealers.push(world::Stealer{x:3, y:0});
+        world.dice = dice::Dice::new(seed);
+        world
+    }
+
+    #[test]
+    fn sight_stops_at_walls_and_models() {
+        let mut world = world::World::for_test(&["EEE EE", "EEEEEE"], &[(0, 0, Direction::Right), (0, 1, Direction::Right)]);
+        world.stealers.push(world::Stealer{x:4, y:1});
+        let pos = |world: &world::World, id| world.get_terminator(id).unwrap().pos.clone();
+        assert_eq!(get_line_of_sight(&world, &pos(&world, 0)), vec![(1, 0), (2, 0)]);
+        assert_eq!(get_target(&world, &pos(&world, 0)), None);
+        assert_eq!(get_line_of_sight(&world, &pos(&world, 1)), vec![(1, 1), (2, 1), (3, 1), (4, 1)]);
+        assert_eq!(get_target(&world, &pos(&world, 1)), Some((4, 1)));
+    }
+
+    #[test]
+    fn shots_kill_on_a_six() {
+        let (mut kills, mut misses) = (0, 0);
+        for seed in 1..40 {
+            let mut world = duel(seed);
+            let entries = shoot(&mut world, 0);
+            let rolls = match &entries[0] {
+                LogEntry::Shot{x:3, y:0, rolls, ..} => rolls.clone(),
+                _ => panic!("no shot logged"),
+            };
+            assert_eq!(rolls.len(), 2);
+            if rolls.contains(&6) {
+                assert!(matches!(entries[1], LogEntry::Killed{x:3, y:0}));
+                assert!(world.stealers.is_empty());
+                kills += 1;
+            }
+            else {
+                assert!(matches!(entries[1], LogEntry::Missed{x:3, y:0}));
+                assert_eq!(world.stealers.len(), 1);
+                misses += 1;
+            }
+            assert_eq!(entries.len(), 2);
+            assert_eq!(world.get_terminator(0).unwrap().aps, world::ACTION_POINTS - 1);
+        }
+        assert!(kills > 0 && misses > 0);
+    }
+
+    #[test]
+    fn doubles_on_overwatch_jam_until_cleared() {
+        let mut jams = 0;
+        for seed in 1..40 {
+            let mut world = duel(seed);
+            let mut log = CommandLog::new();
+            assert!(log.execute(&mut world, Command::Overwatch{id:0}).is_some());
+            let entries = log.execute(&mut world, Command::Shoot{id:0}).unwrap();
+            let doubles = matches!(&entries[0], LogEntry::Shot{rolls, ..} if rolls[0] == rolls[1]);
+            assert_eq!(world.get_terminator(0).unwrap().jammed, doubles);
+            if !doubles {
+                assert!(!Command::ClearJam{id:0}.can_apply(&world));
+                continue;
+            }
+            jams += 1;
+            assert!(matches!(entries.last(), Some(LogEntry::Jammed{..})));
+            assert!(!Command::Shoot{id:0}.can_apply(&world));
+            let aps = world.get_terminator(0).unwrap().aps;
+            assert!(log.execute(&mut world, Command::ClearJam{id:0}).is_some());
+            assert_eq!(world.get_terminator(0).unwrap().aps, aps - 1);
+            assert!(!world.get_terminator(0).unwrap().jammed);
+            assert!(log.undo(&mut world));
+            assert!(world.get_terminator(0).unwrap().jammed);
+        }
+        assert!(jams > 0);
+    }
+
+    #[test]
+    fn shots_never_jam_off_overwatch() {
+        for seed in 1..40 {
+            let mut world = duel(seed);
+            shoot(&mut world, 0);
+            assert!(!world.get_terminator(0).unwrap().jammed);
+        }
+    }
+
+    #[test]
+    fn seen_blips_become_genestealers() {
+        let mut world = world::World::for_test(&["EEEE", "EEEE"], &[(0, 1, Direction::Right)]);
+        world.blips.push(world::Blip{x:3, y:0, stealers:2});
+        assert!(reveal_blips(&mut world).is_empty());
+
+        let mut log = CommandLog::new();
+        let entries = log.execute(&mut world, Command::Move{id:0, dx:0, dy:-1}).unwrap();
+        assert!(matches!(entries.last(), Some(LogEntry::BlipRevealed{x:3, y:0, stealers:2, ..})));
+        assert!(world.blips.is_empty());
+        let stealers: Vec<(u32, u32)> = world.stealers.iter().map(|s| (s.x, s.y)).collect();
+        assert_eq!(stealers, vec![(3, 0), (3, 1)]);
+    }
+
+    #[test]
+    fn dice_and_reveals_lock_undo() {
+        let mut world = world::World::for_test(&["EEEE", "EEEE"], &[(0, 1, Direction::Right)]);
+        world.blips.push(world::Blip{x:3, y:0, stealers:1});
+        let mut log = CommandLog::new();
+        log.execute(&mut world, Command::Rotate{id:0, rotation:Rotation::Left});
+        assert!(log.can_undo());
+        log.execute(&mut world, Command::Rotate{id:0, rotation:Rotation::Right});
+        log.execute(&mut world, Command::Move{id:0, dx:0, dy:-1});
+        assert!(!log.can_undo());
+
+        let mut world = duel(1);
+        let mut log = CommandLog::new();
+        log.execute(&mut world, Command::Rotate{id:0, rotation:Rotation::Left});
+        log.execute(&mut world, Command::Rotate{id:0, rotation:Rotation::Right});
+        log.execute(&mut world, Command::Shoot{id:0});
+        assert!(!log.can_undo() && !log.undo(&mut world));
+        log.execute(&mut world, Command::EndTurn);
+        assert!(!log.can_undo());
+    }
+}
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+
+use super::log_entry::LogEntry;
+use super::world;
+use super::combat;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Rotation {
+    Left,
+    Right,
+}
+
+/// An action changing the world, recorded in a `CommandLog`
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Command {
+    Move { id: u32, dx: i32, dy: i32 },
+    Rotate { id: u32, rotation: Rotation },
+    Shoot { id: u32 },
+    Overwatch { id: u32 },
+    ClearJam { id: u32 },
+    EndTurn,
+}
+
+/// Action points spent going on overwatch
+const OVERWATCH_APS: u32 = 2;
+
+impl Command {
+    pub fn can_apply(&self, world: &world::World) -> bool {
+        match self {
+            Command::Move { id, dx, dy } => match world.get_terminator(*id) {
+                Some(t) => t.aps > 0 && world.can_move(&t.pos, *dx, *dy),
+                None => false,
+            },
+            Command::Rotate { id, .. } => world.get_terminator(*id).is_some(),
+            Command::Shoot { id } => match world.get_terminator(*id) {
+                Some(t) => t.aps > 0 && !t.jammed && combat::get_target(world, &t.pos).is_some(),
+                None => false,
+            },
+            Command::Overwatch { id } => match world.get_terminator(*id) {
+                Some(t) => t.aps >= OVERWATCH_APS && !t.overwatch,
+                None => false,
+            },
+            Command::ClearJam { id } => match world.get_terminator(*id) {
+                Some(t) => t.aps > 0 && t.jammed,
+                None => false,
+            },
+            Command::EndTurn => true,
+        }
+    }
+
+    /// Change the world, telling what happened
+    pub fn apply(&self, world: &mut world::World) -> Vec<LogEntry> {
+        let mut entries = vec![];
+        match self {
+            Command::Move { id, dx, dy } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    t.pos.move_by(*dx, *dy);
+                    t.aps -= 1;
+                    entries.push(LogEntry::Moved{unit:t.name.clone(), x:t.pos.x, y:t.pos.y});
+                }
+                entries.extend(combat::reveal_blips(world));
+            }
+            Command::Rotate { id, rotation } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    match rotation {
+                        Rotation::Left => t.pos.rotate_left(),
+                        Rotation::Right => t.pos.rotate_right(),
+                    }
+                    entries.push(LogEntry::Turned{unit:t.name.clone(), left:matches!(rotation, Rotation::Left)});
+                }
+                entries.extend(combat::reveal_blips(world));
+            }
+            Command::Shoot { id } => entries.extend(combat::shoot(world, *id)),
+            Command::Overwatch { id } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    t.overwatch = true;
+                    t.aps -= OVERWATCH_APS;
+                    entries.push(LogEntry::Overwatch{unit:t.name.clone()});
+                }
+            }
+            Command::ClearJam { id } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    t.jammed = false;
+                    t.aps -= 1;
+                    entries.push(LogEntry::JamCleared{unit:t.name.clone()});
+                }
+            }
+            Command::EndTurn => entries.push(world.end_turn()),
+        }
+        entries
+    }
+
+    /// Only called on reversible commands
+    pub fn revert(&self, world: &mut world::World) {
+        match self {
+            Command::Move { id, dx, dy } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    t.pos.move_by(-dx, -dy);
+                    t.aps += 1;
+                }
+            }
+            Command::Rotate { id, rotation } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    match rotation {
+                        Rotation::Left => t.pos.rotate_right(),
+                        Rotation::Right => t.pos.rotate_left(),
+                    }
+                }
+            }
+            Command::Overwatch { id } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    t.overwatch = false;
+                    t.aps += OVERWATCH_APS;
+                }
+            }
+            Command::ClearJam { id } => {
+                if let Some(t) = world.get_terminator_mut(*id) {
+                    t.jammed = true;
+                    t.aps += 1;
+                }
+            }
+            Command::Shoot { .. } | Command::EndTurn => {}
+        }
+    }
+}
+
+/// What the player did, undo and redo included, in order
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Step {
+    Execute(Command),
+    Undo,
+    Redo,
+}
+
+/// History of executed commands, with undo/redo down to the last irreversible one
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CommandLog {
+    done: Vec<Command>,
+    undone: Vec<Command>,
+    locked: usize,
+    steps: Vec<Step>,
+}
+
+impl CommandLog {
+    pub fn new() -> CommandLog {
+        CommandLog{done:vec![], undone:vec![], locked:0, steps:vec![]}
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Take `step` again, returning what happened if it could be taken
+    pub fn apply_step(&mut self, world: &mut world::World, step: &Step) -> Option<Vec<LogEntry>> {
+        match step {
+            Step::Execute(command) => self.execute(world, command.clone()),
+            Step::Undo => self.undo(world).then(|| vec![LogEntry::Undone]),
+            Step::Redo => self.redo(world).then(|| vec![LogEntry::Redone]),
+        }
+    }
+
+    /// Apply `command` if possible, locking undo once dice are rolled or blips revealed
+    pub fn execute(&mut self, world: &mut world::World, command: Command) -> Option<Vec<LogEntry>> {
+        if !command.can_apply(world) {
+            return None;
+        }
+        let entries = command.apply(world);
+        self.steps.push(Step::Execute(command.clone()));
+        self.done.push(command);
+        self.undone.clear();
+        if entries.iter().any(|e| e.locks_undo()) {
+            self.locked = self.done.len();
+        }
+        Some(entries)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.done.len() > self.locked
+    }
+
+    pub fn undo(&mut self, world: &mut world::World) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        match self.done.pop() {
+            Some(command) => {
+                command.revert(world);
+                self.steps.push(Step::Undo);
+                self.undone.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn redo(&mut self, world: &mut world::World) -> bool {
+        match self.undone.pop() {
+            Some(command) => {
+                command.apply(world);
+                self.steps.push(Step::Redo);
+                self.done.push(command);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::position;
+
+    fn position(world: &world::World, id: u32) -> (u32, u32, u32) {
+        let t = world.get_terminator(id).unwrap();
+        (t.pos.x, t.pos.y, t.aps)
+    }
+
+    #[test]
+    fn undo_and_redo_a_move() {
+        let mut world = world::World::for_test(&["EEEE"], &[(0, 0, position::Direction::Right)]);
+        let mut log = CommandLog::new();
+        assert!(log.execute(&mut world, Command::Move{id:0, dx:1, dy:0}).is_some());
+        assert_eq!(position(&world, 0), (1, 0, world::ACTION_POINTS - 1));
+
+        assert!(log.undo(&mut world));
+        assert_eq!(position(&world, 0), (0, 0, world::ACTION_POINTS));
+        assert!(!log.undo(&mut world));
+
+        assert!(log.redo(&mut world));
+        assert_eq!(position(&world, 0), (1, 0, world::ACTION_POINTS - 1));
+        assert!(!log.redo(&mut world));
+        assert_eq!(log.steps().len(), 3);
+    }
+
+    #[test]
+    fn new_command_drops_redo() {
+        let mut world = world::World::for_test(&["EEEE"], &[(0, 0, position::Direction::Right)]);
+        let mut log = CommandLog::new();
+        log.execute(&mut world, Command::Move{id:0, dx:1, dy:0});
+        log.undo(&mut world);
+        log.execute(&mut world, Command::Rotate{id:0, rotation:Rotation::Left});
+        assert!(!log.redo(&mut world));
+        assert_eq!(position(&world, 0), (0, 0, world::ACTION_POINTS));
+        assert!(log.can_undo());
+    }
+
+    #[test]
+    fn impossible_command_is_not_recorded() {
+        let mut world = world::World::for_test(&["E E"], &[(0, 0, position::Direction::Right)]);
+        let mut log = CommandLog::new();
+        assert!(log.execute(&mut world, Command::Move{id:0, dx:1, dy:0}).is_none());
+        assert!(log.execute(&mut world, Command::Move{id:7, dx:0, dy:0}).is_none());
+        assert!(log.steps().is_empty());
+        assert!(!log.can_undo());
+    }
+}
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// Seeded six-sided dice, kept in the world so that saved games roll the same
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Dice {
+    state: u64,
+}
+
+impl Dice {
+    pub fn new(seed: u64) -> Dice {
+        // xorshift gets stuck on 0
+        Dice{state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed }}
+    }
+
+    pub fn roll(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state % 6) as u32 + 1
+    }
+}
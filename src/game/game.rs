@@ -1,34 +1,246 @@
 use crate::graphics;
 use crate::events;
 use super::world;
+use super::commands;
 use super::handlers;
+use super::input::GameInput;
+use super::replay;
+use super::status;
+use super::log_entry::LogEntry;
 use super::handler_move_terminator::MoveTerminatorHandler;
-use crossterm::event::KeyEvent;
+use super::handler_replay::ReplayHandler;
+use serde::{Deserialize, Serialize};
+use log::{error, info};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_SAVE_PATH: &str = "shulk.sav";
 
 pub struct GameUpdate {
     pub visuals: Vec<graphics::Visual>,
     pub texts: Vec<graphics::Text>,
+    /// What to animate on the board, oldest first
+    pub effects: Vec<graphics::Effect>,
+    pub selected: Option<u32>,
+    pub unit: Option<status::UnitStatus>,
+    pub turn: status::TurnStatus,
+    /// What happened during this event, oldest first
+    pub log: Vec<LogEntry>,
     pub quit: bool,
 }
 
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    start: world::World,
+    world: world::World,
+    log: commands::CommandLog,
+}
+
 pub struct Game {
+    start: world::World,
     world: world::World,
+    log: commands::CommandLog,
     handler: Box<dyn handlers::GameHandler>,
+    /// Where Save and Load go, if anywhere
+    save_path: Option<PathBuf>,
+    replay_path: Option<PathBuf>,
+    /// Steps already written to the replay file
+    recorded: usize,
 }
 
 impl Game {
+    /// A clock-seeded game recorded to a new replay file, hence no `Default`
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Game {
-        let world = world::World::new();
-        let handler = Box::new(MoveTerminatorHandler{});
-        Game{world:world, handler:handler}
+        let mut game = Game::with_seed(clock_seed());
+        game.replay_path = Some(replay::new_replay_path());
+        game
+    }
+
+    /// A new game rolling dice from `seed`, not recorded to any replay file
+    pub fn with_seed(seed: u64) -> Game {
+        Game::from_world(world::World::new(seed))
+    }
+
+    /// A new game starting from `world`, not recorded to any replay file
+    pub fn from_world(world: world::World) -> Game {
+        let handler = Box::new(MoveTerminatorHandler::new());
+        Game{start:world.clone(), world, log:commands::CommandLog::new(), handler,
+            save_path:Some(PathBuf::from(DEFAULT_SAVE_PATH)), replay_path:None, recorded:0}
+    }
+
+    /// Send Save and Load to `path`, or ignore them if none
+    pub fn set_save_path(&mut self, path: Option<PathBuf>) {
+        self.save_path = path;
+    }
+
+    pub fn set_replay_path(&mut self, path: Option<PathBuf>) {
+        self.replay_path = path;
+        self.recorded = 0;
+    }
+
+    /// Where the game is recorded, if anywhere
+    pub fn replay_path(&self) -> Option<&Path> {
+        self.replay_path.as_deref()
+    }
+
+    pub fn world(&self) -> &world::World {
+        &self.world
+    }
+
+    pub fn log(&self) -> &commands::CommandLog {
+        &self.log
+    }
+
+    /// Resume a saved game, later saves going back to the same file, not recorded to any replay file
+    pub fn load(path: &Path) -> Result<Game, Box<dyn std::error::Error>> {
+        let saved = read_save(path)?;
+        let handler = Box::new(MoveTerminatorHandler::new());
+        Ok(Game{start:saved.start, world:saved.world, log:saved.log, handler,
+            save_path:Some(path.to_path_buf()), replay_path:None, recorded:0})
     }
 
-    pub fn handle_event(&mut self, event: events::Event<KeyEvent>) -> GameUpdate {
-        let results = self.handler.handle_event(&mut self.world, event);
+    /// Watch a recorded game, without recording it again
+    pub fn replay(path: &Path) -> Result<Game, Box<dyn std::error::Error>> {
+        let replay = replay::Replay::read(path)?;
+        let world = replay.start.clone();
+        let handler = Box::new(ReplayHandler::new(replay));
+        Ok(Game{start:world.clone(), world, log:commands::CommandLog::new(), handler,
+            save_path:Some(PathBuf::from(DEFAULT_SAVE_PATH)), replay_path:None, recorded:0})
+    }
+
+    pub fn handle_event(&mut self, event: events::Event<GameInput>) -> GameUpdate {
+        let steps = self.log.steps().len();
+        let mut results = self.handler.handle_event(&mut self.world, &mut self.log, event);
+        if self.log.steps().len() != steps {
+            self.record();
+        }
         let quit = match results.events {
             handlers::EventHandling::Quit => true,
+            handlers::EventHandling::Save => {
+                let message = match &self.save_path {
+                    Some(path) => match write_save(path, &self.start, &self.world, &self.log) {
+                        Ok(()) => format!("Game saved to {}", path.display()),
+                        Err(e) => format!("Cannot save game to {}: {}", path.display(), e),
+                    },
+                    None => "Saving is disabled".to_string(),
+                };
+                results = self.handler.view(&self.world);
+                results.log.push(LogEntry::Info(message));
+                false
+            }
+            handlers::EventHandling::Load => {
+                let message = match self.save_path.clone() {
+                    Some(path) => match read_save(&path) {
+                        Ok(saved) => {
+                            self.start = saved.start;
+                            self.world = saved.world;
+                            self.log = saved.log;
+                            // Selection, planned move and popups belonged to the previous world
+                            self.handler = Box::new(MoveTerminatorHandler::new());
+                            if self.replay_path.is_some() {
+                                self.set_replay_path(Some(replay::new_replay_path()));
+                                self.record();
+                            }
+                            format!("Game loaded from {}", path.display())
+                        }
+                        Err(e) => format!("Cannot load game from {}: {}", path.display(), e),
+                    },
+                    None => "Loading is disabled".to_string(),
+                };
+                results = self.handler.view(&self.world);
+                results.log.push(LogEntry::Info(message));
+                false
+            }
             _ => false,
         };
-        GameUpdate{visuals:results.visuals, texts:results.texts, quit:quit}
+        for entry in results.log.iter() {
+            info!("{}", entry);
+        }
+        let unit = results.selected.and_then(|id| status::UnitStatus::from_world(&self.world, id));
+        let turn = status::TurnStatus::from_world(&self.world, &self.log);
+        GameUpdate{visuals:results.visuals, texts:results.texts, effects:results.effects, selected:results.selected,
+            unit, turn, log:results.log, quit}
+    }
+
+    /// Write the steps taken since the last call, starting the replay file on the first one.
+    /// The file is renamed if another one already has its name.
+    fn record(&mut self) {
+        if let Some(path) = &self.replay_path {
+            let steps = self.log.steps();
+            let result = if self.recorded == 0 {
+                replay::Replay{start:self.start.clone(), steps:steps.to_vec()}.write(path)
+            }
+            else {
+                replay::Replay::append(path, &steps[self.recorded..]).map(|()| path.clone())
+            };
+            match result {
+                Ok(written) => {
+                    self.recorded = steps.len();
+                    self.replay_path = Some(written);
+                }
+                Err(e) => error!("Cannot record replay to {}: {}", path.display(), e),
+            }
+        }
+    }
+}
+
+/// A seed for games not asking for a specific one
+pub fn clock_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0)
+}
+
+fn write_save(path: &Path, start: &world::World, world: &world::World, log: &commands::CommandLog) -> Result<(), Box<dyn std::error::Error>> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(writer, &SavedGame{start:start.clone(), world:world.clone(), log:log.clone()})?;
+    Ok(())
+}
+
+fn read_save(path: &Path) -> Result<SavedGame, Box<dyn std::error::Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::handler_move_terminator;
+    use crate::game::position::Direction;
+    use std::fs;
+
+    fn send(game: &mut Game, input: GameInput) -> GameUpdate {
+        game.handle_event(events::Event::Input(input))
+    }
+
+    fn has_popup(update: &GameUpdate) -> bool {
+        update.texts.iter().any(|t| matches!(t, graphics::Text::Popup(..)))
+    }
+
+    #[test]
+    fn saving_leaves_timers_alone_and_loading_resets_the_handler() {
+        let path = std::env::temp_dir().join(format!("shulk-test-game-{}.sav", std::process::id()));
+        let mut world = world::World::for_test(&["EEEEE", "EEEEE"], &[(0, 0, Direction::Right)]);
+        world.stealers.push(world::Stealer{x:3, y:0});
+        let mut game = Game::from_world(world);
+        game.set_save_path(Some(path.clone()));
+        send(&mut game, GameInput::Save);
+
+        send(&mut game, GameInput::Select(0));
+        assert!(has_popup(&send(&mut game, GameInput::Shoot)));
+        for _ in 1..handler_move_terminator::POPUP_TICKS {
+            game.handle_event(events::Event::Tick);
+        }
+        assert!(has_popup(&send(&mut game, GameInput::Save)));
+        assert!(!has_popup(&game.handle_event(events::Event::Tick)));
+
+        send(&mut game, GameInput::Target(4, 1));
+        let planned = |update: &GameUpdate| update.visuals.iter().any(|v| v.layer == graphics::Layer::Cursor);
+        assert!(planned(&game.handle_event(events::Event::Tick)));
+        let update = send(&mut game, GameInput::Load);
+        fs::remove_file(&path).unwrap();
+        assert!(!planned(&update));
+        assert_eq!(update.log.last().unwrap().to_string(), format!("Game loaded from {}", path.display()));
     }
 }
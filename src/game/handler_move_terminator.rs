@@ -1,65 +1,410 @@
 use crate::events;
 use crate::graphics;
 use crate::game::world;
+use crate::game::commands;
+use crate::game::combat;
+use crate::game::log_entry::LogEntry;
+use crate::game::input::GameInput;
 use super::handlers;
-use crossterm::{
-    event::{KeyCode, KeyEvent},
-};
+
+/// Phase of the turn played with this handler, shown in the title
+const PHASE: &str = "marines' action phase";
+/// Ticks a popup stays on the board
+pub const POPUP_TICKS: u32 = 40;
+
+/// Dice rolls and results shown over a square for a while
+struct Popup {
+    x: u32,
+    y: u32,
+    text: String,
+    ticks: u32,
+}
 
 pub struct MoveTerminatorHandler {
+    selected: u32,
+    popups: Vec<Popup>,
+    /// Square the selected unit would move to, moved by the movement keys while planning
+    destination: Option<(u32, u32)>,
 }
 
-impl handlers::GameHandler for MoveTerminatorHandler {
-    fn handle_event(&mut self, world: &mut world::World, event: events::Event<KeyEvent>) -> handlers::EventUpdate {
-        let mut texts = Vec::<graphics::Text>::new();
-        for t in &world.terminators {
-            let text = graphics::Text::ItemAnnotation(t.id, t.name.clone());
-            texts.push(text);
+impl Default for MoveTerminatorHandler {
+    fn default() -> MoveTerminatorHandler {
+        MoveTerminatorHandler::new()
+    }
+}
+
+impl MoveTerminatorHandler {
+    pub fn new() -> MoveTerminatorHandler {
+        MoveTerminatorHandler{selected:4, popups:vec![], destination:None}
+    }
+
+    /// Commands following the planned path of the selected unit, `None` if its action points do not reach the end
+    fn get_path_commands(&self, world: &world::World) -> Option<Vec<commands::Command>> {
+        let (x, y) = self.destination?;
+        let path = world.get_terminator(self.selected).and_then(|t| world.get_path(self.selected, x, y, t.aps))?;
+        let steps = path.windows(2).map(|step| {
+            if step[0].dir != step[1].dir {
+                let rotation = if step[0].dir.rotate_left() == step[1].dir { commands::Rotation::Left } else { commands::Rotation::Right };
+                commands::Command::Rotate{id:self.selected, rotation}
+            }
+            else {
+                commands::Command::Move{id:self.selected, dx:step[1].x as i32 - step[0].x as i32, dy:step[1].y as i32 - step[0].y as i32}
+            }
+        }).collect();
+        Some(steps)
+    }
+
+    /// Show what the entries rolled or revealed over their squares, after anything still shown there
+    fn add_popups(&mut self, entries: &[LogEntry]) {
+        for entry in entries.iter() {
+            let (x, y, text) = match entry {
+                LogEntry::Shot{x, y, rolls, ..} => {
+                    let rolls: Vec<String> = rolls.iter().map(|r| r.to_string()).collect();
+                    (*x, *y, rolls.join(" "))
+                }
+                LogEntry::Killed{x, y} => (*x, *y, "Killed".to_string()),
+                LogEntry::Missed{x, y} => (*x, *y, "Missed".to_string()),
+                LogEntry::BlipRevealed{x, y, stealers, ..} => (*x, *y, format!("{} stealers", stealers)),
+                _ => continue,
+            };
+            match self.popups.iter_mut().find(|p| p.x == x && p.y == y) {
+                Some(popup) => {
+                    popup.text = format!("{} {}", popup.text, text);
+                    popup.ticks = POPUP_TICKS;
+                }
+                None => self.popups.push(Popup{x, y, text, ticks:POPUP_TICKS}),
+            }
         }
+    }
 
-        let selected = 4;
-        let mut pos = world.terminators[selected].pos.clone();
-        let mut aps = world.terminators[selected].aps;
+    fn select_next(&mut self, world: &world::World) {
+        let next = world.terminators.iter()
+            .position(|t| t.id == self.selected)
+            .map(|i| (i + 1) % world.terminators.len())
+            .and_then(|i| world.terminators.get(i));
+        if let Some(t) = next.or_else(|| world.terminators.first()) {
+            self.selected = t.id;
+        }
+    }
+}
+
+impl handlers::GameHandler for MoveTerminatorHandler {
+    fn handle_event(&mut self, world: &mut world::World, log: &mut commands::CommandLog, event: events::Event<GameInput>) -> handlers::EventUpdate {
+        if world.get_terminator(self.selected).is_none() {
+            self.select_next(world);
+        }
+        let selected = self.selected;
+        let mut log_entries = Vec::<LogEntry>::new();
+        let mut effects = Vec::<graphics::Effect>::new();
+        let mut actions = Vec::<commands::Command>::new();
         match event {
-            events::Event::Input(key_event) => match key_event.code {
-                KeyCode::Char('q') => {
+            events::Event::Input(input) if self.destination.is_some() && is_planning_input(input) => {
+                if let Some((x, y)) = self.destination {
+                    let (w, h) = world.board.get_size();
+                    self.destination = match input {
+                        GameInput::MoveForward => Some((x, y.saturating_sub(1))),
+                        GameInput::MoveBackward => Some((x, (y + 1).min(h.saturating_sub(1)))),
+                        GameInput::TurnLeft => Some((x.saturating_sub(1), y)),
+                        GameInput::TurnRight => Some(((x + 1).min(w.saturating_sub(1)), y)),
+                        GameInput::Confirm => {
+                            match self.get_path_commands(world) {
+                                Some(path) => actions = path,
+                                None => log_entries.push(LogEntry::Info("Destination out of reach".to_string())),
+                            }
+                            None
+                        }
+                        _ => None,
+                    };
+                }
+            }
+            events::Event::Input(input) => match input {
+                GameInput::Quit => {
                     return handlers::EventUpdate::quit();
                 }
-                KeyCode::Up => {
-                    let (dx, dy) = pos.dir.move_frontward();
-                    if aps > 0 && world.can_move(&pos, dx, dy) {
-                        pos.move_by(dx, dy);
-                        aps -= 1;
+                GameInput::Save => {
+                    return handlers::EventUpdate::save();
+                }
+                GameInput::Load => {
+                    return handlers::EventUpdate::load();
+                }
+                GameInput::Undo => {
+                    let before = world.terminators.clone();
+                    if log.undo(world) {
+                        log_entries.push(LogEntry::Undone);
+                        effects.extend(get_effects(&before, world, &log_entries));
                     }
                 }
-                KeyCode::Down => {
-                    let (dx, dy) = pos.dir.move_backward();
-                    if aps > 0 && world.can_move(&pos, dx, dy) {
-                        pos.move_by(dx, dy);
-                        aps -= 1;
+                GameInput::Redo => {
+                    let before = world.terminators.clone();
+                    if log.redo(world) {
+                        log_entries.push(LogEntry::Redone);
+                        effects.extend(get_effects(&before, world, &log_entries));
                     }
                 }
-                KeyCode::Left => {
-                    pos.rotate_left();
+                GameInput::Shoot => {
+                    actions.push(commands::Command::Shoot{id:selected});
+                }
+                GameInput::Overwatch => {
+                    actions.push(commands::Command::Overwatch{id:selected});
                 }
-                KeyCode::Right => {
-                    pos.rotate_right();
+                GameInput::ClearJam => {
+                    actions.push(commands::Command::ClearJam{id:selected});
+                }
+                GameInput::EndTurn => {
+                    actions.push(commands::Command::EndTurn);
+                }
+                GameInput::PlanMove => {
+                    self.destination = world.get_terminator(selected).map(|t| (t.pos.x, t.pos.y));
+                }
+                GameInput::Select(id) if world.get_terminator(id).is_some() => {
+                    self.selected = id;
+                    self.destination = None;
+                }
+                GameInput::SelectNext => {
+                    self.select_next(world);
+                    self.destination = None;
+                }
+                GameInput::Target(x, y) => {
+                    match world.get_terminator_at(x, y) {
+                        Some(t) => {
+                            self.selected = t.id;
+                            self.destination = None;
+                        }
+                        None => self.destination = Some((x, y)),
+                    }
+                }
+                GameInput::MoveForward => {
+                    if let Some(t) = world.get_terminator(selected) {
+                        let (dx, dy) = t.pos.dir.move_frontward();
+                        actions.push(commands::Command::Move{id:selected, dx, dy});
+                    }
+                }
+                GameInput::MoveBackward => {
+                    if let Some(t) = world.get_terminator(selected) {
+                        let (dx, dy) = t.pos.dir.move_backward();
+                        actions.push(commands::Command::Move{id:selected, dx, dy});
+                    }
+                }
+                GameInput::TurnLeft => {
+                    actions.push(commands::Command::Rotate{id:selected, rotation:commands::Rotation::Left});
+                }
+                GameInput::TurnRight => {
+                    actions.push(commands::Command::Rotate{id:selected, rotation:commands::Rotation::Right});
                 }
                 _ => {}
             },
-            events::Event::Tick => {}
+            events::Event::Tick => {
+                for popup in self.popups.iter_mut() {
+                    popup.ticks -= 1;
+                }
+                self.popups.retain(|p| p.ticks > 0);
+            }
+            events::Event::Resize(..) => {}
+        }
+        // Up to the first command that cannot be applied
+        for command in actions {
+            let before = world.terminators.clone();
+            match log.execute(world, command) {
+                Some(entries) => {
+                    effects.extend(get_effects(&before, world, &entries));
+                    log_entries.extend(entries);
+                }
+                None => break,
+            }
+        }
+        self.add_popups(&log_entries);
+
+        let update = self.view(world);
+        handlers::EventUpdate{effects, log:log_entries, ..update}
+    }
+
+    fn view(&self, world: &world::World) -> handlers::EventUpdate {
+        let mut texts = handlers::get_board_texts(world);
+        let turn = format!("turn {} - {} - {} CP", world.turn, PHASE, world.cps);
+        texts.push(graphics::Text::Title(if world.name.is_empty() { turn } else { format!("{} - {}", world.name, turn) }));
+        for p in self.popups.iter() {
+            texts.push(graphics::Text::Popup(p.x, p.y, p.text.clone()));
         }
-        world.terminators[selected].pos = pos;
-        world.terminators[selected].aps = aps;
 
-        let visuals = world.get_simple_visuals();
-        handlers::EventUpdate{visuals:visuals, texts:texts, events:handlers::EventHandling::Keep}
+        let mut visuals = world.get_simple_visuals();
+        visuals.extend(world.get_move_visuals(self.selected, self.destination));
+        visuals.extend(world.get_fire_visuals(Some(self.selected)));
+        let selected = world.get_terminator(self.selected).map(|t| t.id);
+        handlers::EventUpdate{visuals, texts, effects:vec![], selected, log:vec![], events:handlers::EventHandling::Keep}
     }
 }
 
+/// What to animate once `entries` happened, terminators sliding from where they stood in `before`
+fn get_effects(before: &[world::Terminator], world: &world::World, entries: &[LogEntry]) -> Vec<graphics::Effect> {
+    let mut effects = vec![];
+    for t in world.terminators.iter() {
+        if let Some(b) = before.iter().find(|b| b.id == t.id && (b.pos.x, b.pos.y) != (t.pos.x, t.pos.y)) {
+            let visual = b.get_visual();
+            effects.push(graphics::Effect::Slide{content:visual.content, color:visual.color, from:(b.pos.x, b.pos.y), to:(t.pos.x, t.pos.y)});
+        }
+    }
+    for entry in entries.iter() {
+        match entry {
+            LogEntry::Shot{unit, x, y, ..} => {
+                let t = match world.terminators.iter().find(|t| t.name == *unit) {
+                    Some(t) => t,
+                    None => continue,
+                };
+                // The line of fire ends at the target, even once killed
+                let mut squares = combat::get_line_of_sight(world, &t.pos);
+                if let Some(i) = squares.iter().position(|s| *s == (*x, *y)) {
+                    squares.truncate(i + 1);
+                }
+                effects.push(match t.weapon_kind {
+                    world::WeaponKind::Gun => graphics::Effect::Fire{squares},
+                    world::WeaponKind::Flamer => graphics::Effect::Flame{squares},
+                });
+            }
+            LogEntry::Killed{x, y} => {
+                let visual = world::Stealer{x:*x, y:*y}.get_visual();
+                effects.push(graphics::Effect::Fade{content:visual.content, color:visual.color, x:*x, y:*y});
+            }
+            _ => {}
+        }
+    }
+    effects
+}
+
+/// Inputs moving the destination, confirming or cancelling it while planning a move
+fn is_planning_input(input: GameInput) -> bool {
+    matches!(input, GameInput::MoveForward | GameInput::MoveBackward | GameInput::TurnLeft | GameInput::TurnRight
+        | GameInput::Confirm | GameInput::PlanMove)
+}
+
 // self.a = build_value(self.a)
 // self.a.value()
 // self.a = self.build_value(a);
 // ! self.a = self.build_value(self.a) -> ?
 // ! self.change_value(self.a) -> ?
 // ! let &mut S = &mut self -> It cannot prove self is still valid when you use S
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::handlers::GameHandler;
+    use crate::game::position::Direction;
+
+    fn send(handler: &mut MoveTerminatorHandler, world: &mut world::World, log: &mut commands::CommandLog, input: GameInput) -> handlers::EventUpdate {
+        handler.handle_event(world, log, events::Event::Input(input))
+    }
+
+    #[test]
+    fn inputs_become_commands() {
+        let mut world = world::World::for_test(&["EEEE", "EEEE"], &[(0, 0, Direction::Right), (0, 1, Direction::Right)]);
+        let mut log = commands::CommandLog::new();
+        let mut handler = MoveTerminatorHandler::new();
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Select(1));
+        assert_eq!(update.selected, Some(1));
+        assert!(log.steps().is_empty());
+
+        let update = send(&mut handler, &mut world, &mut log, GameInput::MoveForward);
+        assert_eq!(log.steps(), &[commands::Step::Execute(commands::Command::Move{id:1, dx:1, dy:0})]);
+        assert!(matches!(update.log.as_slice(), [LogEntry::Moved{x:1, y:1, ..}]));
+        assert!(matches!(update.effects.as_slice(), [graphics::Effect::Slide{from:(0, 1), to:(1, 1), ..}]));
+        assert!(matches!(update.events, handlers::EventHandling::Keep));
+
+        send(&mut handler, &mut world, &mut log, GameInput::TurnLeft);
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Undo);
+        assert_eq!(log.steps().last(), Some(&commands::Step::Undo));
+        assert!(matches!(update.log.as_slice(), [LogEntry::Undone]));
+        assert_eq!(world.get_terminator(1).unwrap().pos.dir, Direction::Right);
+    }
+
+    #[test]
+    fn impossible_moves_are_not_executed() {
+        let mut world = world::World::for_test(&["E E"], &[(0, 0, Direction::Right)]);
+        let mut log = commands::CommandLog::new();
+        let mut handler = MoveTerminatorHandler::new();
+        let update = send(&mut handler, &mut world, &mut log, GameInput::MoveForward);
+        assert_eq!(update.selected, Some(0));
+        assert!(update.log.is_empty() && update.effects.is_empty());
+        assert!(log.steps().is_empty());
+    }
+
+    #[test]
+    fn quit_save_and_load_are_left_to_the_game() {
+        let mut world = world::World::for_test(&["EE"], &[(0, 0, Direction::Right)]);
+        let mut log = commands::CommandLog::new();
+        let mut handler = MoveTerminatorHandler::new();
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Save);
+        assert!(matches!(update.events, handlers::EventHandling::Save));
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Load);
+        assert!(matches!(update.events, handlers::EventHandling::Load));
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Quit);
+        assert!(matches!(update.events, handlers::EventHandling::Quit));
+        assert!(log.steps().is_empty());
+    }
+
+    #[test]
+    fn title_shows_the_turn_and_phase() {
+        let mut world = world::World::for_test(&["EE"], &[(0, 0, Direction::Right)]);
+        let mut log = commands::CommandLog::new();
+        let mut handler = MoveTerminatorHandler::new();
+        send(&mut handler, &mut world, &mut log, GameInput::EndTurn);
+        let update = handler.handle_event(&mut world, &mut log, events::Event::Tick);
+        let title = update.texts.iter().find_map(|t| match t {
+            graphics::Text::Title(title) => Some(title.clone()),
+            _ => None,
+        });
+        assert!(title.unwrap().contains(&format!("turn {} - {}", world.turn, PHASE)));
+    }
+
+    #[test]
+    fn selecting_another_unit_drops_the_planned_move() {
+        let mut world = world::World::for_test(&["EEEE", "EEEE"], &[(0, 0, Direction::Right), (0, 1, Direction::Right)]);
+        let mut log = commands::CommandLog::new();
+        let mut handler = MoveTerminatorHandler::new();
+        send(&mut handler, &mut world, &mut log, GameInput::Select(0));
+        send(&mut handler, &mut world, &mut log, GameInput::Target(3, 0));
+        assert_eq!(handler.destination, Some((3, 0)));
+        send(&mut handler, &mut world, &mut log, GameInput::Select(1));
+        assert_eq!(handler.destination, None);
+
+        send(&mut handler, &mut world, &mut log, GameInput::Target(3, 1));
+        send(&mut handler, &mut world, &mut log, GameInput::SelectNext);
+        assert_eq!((handler.selected, handler.destination), (0, None));
+
+        send(&mut handler, &mut world, &mut log, GameInput::Target(3, 0));
+        send(&mut handler, &mut world, &mut log, GameInput::Target(0, 1));
+        assert_eq!((handler.selected, handler.destination), (1, None));
+        assert!(log.steps().is_empty());
+    }
+
+    #[test]
+    fn undo_and_redo_slide_back_and_forth() {
+        let mut world = world::World::for_test(&["EEEE"], &[(0, 0, Direction::Right)]);
+        let mut log = commands::CommandLog::new();
+        let mut handler = MoveTerminatorHandler::new();
+        send(&mut handler, &mut world, &mut log, GameInput::MoveForward);
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Undo);
+        assert!(matches!(update.effects.as_slice(), [graphics::Effect::Slide{from:(1, 0), to:(0, 0), ..}]));
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Redo);
+        assert!(matches!(update.effects.as_slice(), [graphics::Effect::Slide{from:(0, 0), to:(1, 0), ..}]));
+        let update = send(&mut handler, &mut world, &mut log, GameInput::Redo);
+        assert!(update.effects.is_empty() && update.log.is_empty());
+    }
+
+    #[test]
+    fn weapon_kind_picks_the_effect_of_a_shot() {
+        for kind in &[world::WeaponKind::Gun, world::WeaponKind::Flamer] {
+            let mut world = world::World::for_test(&["EEEEE"], &[(0, 0, Direction::Right)]);
+            world.stealers.push(world::Stealer{x:3, y:0});
+            world.get_terminator_mut(0).unwrap().weapon_kind = *kind;
+            let mut log = commands::CommandLog::new();
+            let mut handler = MoveTerminatorHandler::new();
+            send(&mut handler, &mut world, &mut log, GameInput::Select(0));
+            let update = send(&mut handler, &mut world, &mut log, GameInput::Shoot);
+            let squares = match (&update.effects[0], kind) {
+                (graphics::Effect::Fire{squares}, world::WeaponKind::Gun) => squares,
+                (graphics::Effect::Flame{squares}, world::WeaponKind::Flamer) => squares,
+                _ => panic!("wrong effect for {:?}", kind),
+            };
+            assert_eq!(squares, &vec![(1, 0), (2, 0), (3, 0)]);
+        }
+    }
+}
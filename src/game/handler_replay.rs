@@ -0,0 +1,123 @@
+use crate::events;
+use crate::graphics;
+use crate::game::world;
+use crate::game::commands;
+use crate::game::replay;
+use crate::game::input::GameInput;
+use crate::game::log_entry::LogEntry;
+use super::handlers;
+
+/// Number of ticks between two steps while playing
+const TICKS_PER_STEP: u32 = 15;
+
+/// Plays a recorded game back, step by step
+pub struct ReplayHandler {
+    replay: replay::Replay,
+    turn_starts: Vec<usize>,
+    current: usize,
+    paused: bool,
+    ticks: u32,
+    typed_turn: Option<usize>,
+}
+
+impl ReplayHandler {
+    pub fn new(replay: replay::Replay) -> ReplayHandler {
+        let turn_starts = replay.turn_starts();
+        ReplayHandler{replay, turn_starts, current:0, paused:false, ticks:0, typed_turn:None}
+    }
+
+    fn current_turn(&self) -> usize {
+        self.turn_starts.iter().filter(|s| **s <= self.current).count()
+    }
+
+    /// Go to the world after `target` steps, returning what happened on the way forward
+    fn seek(&mut self, world: &mut world::World, log: &mut commands::CommandLog, target: usize) -> Vec<LogEntry> {
+        let target = target.min(self.replay.steps.len());
+        let mut entries = vec![];
+        if target > self.current {
+            for step in &self.replay.steps[self.current..target] {
+                entries.extend(log.apply_step(world, step).unwrap_or_default());
+            }
+        }
+        else if target < self.current {
+            let (w, l) = self.replay.world_at(target);
+            *world = w;
+            *log = l;
+        }
+        self.current = target;
+        entries
+    }
+
+    fn seek_turn(&mut self, world: &mut world::World, log: &mut commands::CommandLog, turn: usize) -> Vec<LogEntry> {
+        if turn == 0 {
+            return vec![];
+        }
+        let index = turn.min(self.turn_starts.len()) - 1;
+        let target = self.turn_starts[index];
+        self.seek(world, log, target)
+    }
+}
+
+impl handlers::GameHandler for ReplayHandler {
+    fn handle_event(&mut self, world: &mut world::World, log: &mut commands::CommandLog, event: events::Event<GameInput>) -> handlers::EventUpdate {
+        let mut log_entries = vec![];
+        match event {
+            events::Event::Input(input) => match input {
+                GameInput::Quit => {
+                    return handlers::EventUpdate::quit();
+                }
+                GameInput::TogglePause => {
+                    self.paused = !self.paused;
+                }
+                GameInput::StepForward => {
+                    self.paused = true;
+                    log_entries = self.seek(world, log, self.current + 1);
+                }
+                GameInput::StepBack => {
+                    self.paused = true;
+                    self.seek(world, log, self.current.saturating_sub(1));
+                }
+                GameInput::NextTurn => {
+                    let turn = self.current_turn() + 1;
+                    log_entries = self.seek_turn(world, log, turn);
+                }
+                GameInput::PreviousTurn => {
+                    let turn = self.current_turn().saturating_sub(1).max(1);
+                    self.seek_turn(world, log, turn);
+                }
+                GameInput::Digit(digit) => {
+                    self.typed_turn = Some(self.typed_turn.unwrap_or(0) * 10 + digit as usize);
+                }
+                GameInput::Confirm => {
+                    if let Some(turn) = self.typed_turn.take() {
+                        self.paused = true;
+                        log_entries = self.seek_turn(world, log, turn);
+                    }
+                }
+                _ => {}
+            },
+            events::Event::Tick => {
+                if !self.paused {
+                    self.ticks += 1;
+                    if self.ticks >= TICKS_PER_STEP {
+                        self.ticks = 0;
+                        log_entries = self.seek(world, log, self.current + 1);
+                    }
+                }
+            }
+            events::Event::Resize(..) => {}
+        }
+
+        handlers::EventUpdate{log:log_entries, ..self.view(world)}
+    }
+
+    fn view(&self, world: &world::World) -> handlers::EventUpdate {
+        let mut texts = handlers::get_board_texts(world);
+        let state = if self.paused { "paused" } else { "playing" };
+        texts.push(graphics::Text::Title(format!("Replay - turn {} - step {}/{} - {}", world.turn, self.current, self.replay.steps.len(), state)));
+
+        let mut visuals = world.get_simple_visuals();
+        visuals.extend(world.get_fire_visuals(None));
+        handlers::EventUpdate{visuals, texts, effects:vec![], selected:None, log:vec![], events:handlers::EventHandling::Keep}
+    }
+}
@@ -1,26 +1,56 @@
 use crate::graphics;
 use crate::events;
 use crate::game::world;
-use crossterm::event::KeyEvent;
+use crate::game::commands;
+use crate::game::log_entry::LogEntry;
+use crate::game::input::GameInput;
 
 pub enum EventHandling {
     Keep,
 //    SetHandler(String),
+    Save,
+    Load,
     Quit,
 }
 
 pub struct EventUpdate {
     pub visuals: Vec<graphics::Visual>,
     pub texts: Vec<graphics::Text>,
+    pub effects: Vec<graphics::Effect>,
+    pub selected: Option<u32>,
+    pub log: Vec<LogEntry>,
     pub events: EventHandling,
 }
 
 impl EventUpdate {
     pub fn quit() -> EventUpdate {
-        EventUpdate{visuals:vec![], texts:vec![], events:EventHandling::Quit}
+        EventUpdate{visuals:vec![], texts:vec![], effects:vec![], selected:None, log:vec![], events:EventHandling::Quit}
+    }
+
+    pub fn save() -> EventUpdate {
+        EventUpdate{visuals:vec![], texts:vec![], effects:vec![], selected:None, log:vec![], events:EventHandling::Save}
+    }
+
+    pub fn load() -> EventUpdate {
+        EventUpdate{visuals:vec![], texts:vec![], effects:vec![], selected:None, log:vec![], events:EventHandling::Load}
     }
 }
 
+/// Unit annotations and square labels of `world`
+pub fn get_board_texts(world: &world::World) -> Vec<graphics::Text> {
+    let mut texts = Vec::<graphics::Text>::new();
+    for t in &world.terminators {
+        texts.push(graphics::Text::ItemDetails(t.id, t.get_details()));
+    }
+    for l in &world.labels {
+        texts.push(graphics::Text::TileLabel(l.x, l.y, l.text.clone()));
+    }
+    texts
+}
+
 pub trait GameHandler {
-    fn handle_event(&mut self, world: &mut world::World, event: events::Event<KeyEvent>) -> EventUpdate;
+    fn handle_event(&mut self, world: &mut world::World, log: &mut commands::CommandLog, event: events::Event<GameInput>) -> EventUpdate;
+
+    /// What to show of `world`, changing nothing: no effects, log entries or events
+    fn view(&self, world: &world::World) -> EventUpdate;
 }
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// What a player, an AI or a remote peer asks the game to do
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum GameInput {
+    MoveForward,
+    MoveBackward,
+    TurnLeft,
+    TurnRight,
+    Select(u32),
+    SelectNext,
+    Target(u32, u32),
+    Shoot,
+    Overwatch,
+    ClearJam,
+    PlanMove,
+    EndTurn,
+    Undo,
+    Redo,
+    Save,
+    Load,
+    Quit,
+    ToggleHelp,
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    Recenter,
+    ScrollLogUp,
+    ScrollLogDown,
+    ToggleOverlays,
+    /// Page through the log file outside of the game
+    ViewLog,
+    // Replay controls
+    TogglePause,
+    StepForward,
+    StepBack,
+    NextTurn,
+    PreviousTurn,
+    Digit(u32),
+    Confirm,
+}
+
+impl fmt::Display for GameInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameInput::Select(id) => write!(f, "Select:{}", id),
+            GameInput::Target(x, y) => write!(f, "Target:{},{}", x, y),
+            GameInput::Digit(d) => write!(f, "Digit:{}", d),
+            _ => write!(f, "{:?}", self),
+        }
+    }
+}
+
+impl FromStr for GameInput {
+    type Err = String;
+
+    /// Parse the `Display` form, e.g. `MoveForward`, `Select:3` or `Target:4,11`
+    fn from_str(s: &str) -> Result<GameInput, String> {
+        let (name, arg) = match s.find(':') {
+            Some(i) => (&s[..i], Some(&s[i+1..])),
+            None => (s, None),
+        };
+        let number = |a: &str| a.trim().parse::<u32>().map_err(|_| format!("invalid number in '{}'", s));
+        let input = match (name, arg) {
+            ("MoveForward", None) => GameInput::MoveForward,
+            ("MoveBackward", None) => GameInput::MoveBackward,
+            ("TurnLeft", None) => GameInput::TurnLeft,
+            ("TurnRight", None) => GameInput::TurnRight,
+            ("Select", Some(a)) => GameInput::Select(number(a)?),
+            ("SelectNext", None) => GameInput::SelectNext,
+            ("Target", Some(a)) => match a.find(',') {
+                Some(i) => GameInput::Target(number(&a[..i])?, number(&a[i+1..])?),
+                None => return Err(format!("'{}' expects x,y", s)),
+            },
+            ("Shoot", None) => GameInput::Shoot,
+            ("Overwatch", None) => GameInput::Overwatch,
+            ("ClearJam", None) => GameInput::ClearJam,
+            ("PlanMove", None) => GameInput::PlanMove,
+            ("EndTurn", None) => GameInput::EndTurn,
+            ("Undo", None) => GameInput::Undo,
+            ("Redo", None) => GameInput::Redo,
+            ("Save", None) => GameInput::Save,
+            ("Load", None) => GameInput::Load,
+            ("Quit", None) => GameInput::Quit,
+            ("ToggleHelp", None) => GameInput::ToggleHelp,
+            ("PanUp", None) => GameInput::PanUp,
+            ("PanDown", None) => GameInput::PanDown,
+            ("PanLeft", None) => GameInput::PanLeft,
+            ("PanRight", None) => GameInput::PanRight,
+            ("Recenter", None) => GameInput::Recenter,
+            ("ScrollLogUp", None) => GameInput::ScrollLogUp,
+            ("ScrollLogDown", None) => GameInput::ScrollLogDown,
+            ("ToggleOverlays", None) => GameInput::ToggleOverlays,
+            ("ViewLog", None) => GameInput::ViewLog,
+            ("TogglePause", None) => GameInput::TogglePause,
+            ("StepForward", None) => GameInput::StepForward,
+            ("StepBack", None) => GameInput::StepBack,
+            ("NextTurn", None) => GameInput::NextTurn,
+            ("PreviousTurn", None) => GameInput::PreviousTurn,
+            ("Digit", Some(a)) => GameInput::Digit(number(a)?),
+            ("Confirm", None) => GameInput::Confirm,
+            _ => return Err(format!("unknown input '{}'", s)),
+        };
+        Ok(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_parses_back() {
+        let inputs = [GameInput::MoveForward, GameInput::Select(3), GameInput::Target(4, 11), GameInput::Digit(7),
+            GameInput::ToggleOverlays, GameInput::ViewLog, GameInput::Confirm];
+        for input in inputs.iter() {
+            assert_eq!(input.to_string().parse::<GameInput>(), Ok(*input));
+        }
+        assert_eq!("Target: 4, 11".parse::<GameInput>(), Ok(GameInput::Target(4, 11)));
+    }
+
+    #[test]
+    fn rejects_malformed_inputs() {
+        assert!("Jump".parse::<GameInput>().is_err());
+        assert!("Select".parse::<GameInput>().is_err());
+        assert!("Select:x".parse::<GameInput>().is_err());
+        assert!("Target:4".parse::<GameInput>().is_err());
+        assert!("Quit:1".parse::<GameInput>().is_err());
+    }
+}
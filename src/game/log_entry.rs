@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Something that happened in the game, for the log panel and the log file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum LogEntry {
+    Moved { unit: String, x: u32, y: u32 },
+    Turned { unit: String, left: bool },
+    Overwatch { unit: String },
+    Shot { unit: String, x: u32, y: u32, rolls: Vec<u32> },
+    Jammed { unit: String },
+    JamCleared { unit: String },
+    Killed { x: u32, y: u32 },
+    Missed { x: u32, y: u32 },
+    BlipRevealed { unit: String, x: u32, y: u32, stealers: u32 },
+    TurnStarted { turn: u32, cps: u32 },
+    Undone,
+    Redone,
+    Info(String),
+}
+
+impl LogEntry {
+    /// Dice rolled or hidden information shown: no going back
+    pub fn locks_undo(&self) -> bool {
+        matches!(self, LogEntry::Shot { .. } | LogEntry::BlipRevealed { .. } | LogEntry::TurnStarted { .. })
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LogEntry::Moved { unit, x, y } => write!(f, "{} moves to ({}, {})", unit, x, y),
+            LogEntry::Turned { unit, left: true } => write!(f, "{} turns left", unit),
+            LogEntry::Turned { unit, left: false } => write!(f, "{} turns right", unit),
+            LogEntry::Overwatch { unit } => write!(f, "{} goes on overwatch", unit),
+            LogEntry::Shot { unit, x, y, rolls } => {
+                let rolls: Vec<String> = rolls.iter().map(|r| r.to_string()).collect();
+                write!(f, "{} fires at ({}, {}), rolls {}", unit, x, y, rolls.join(" "))
+            }
+            LogEntry::Jammed { unit } => write!(f, "{}'s weapon jams", unit),
+            LogEntry::JamCleared { unit } => write!(f, "{} clears the jam", unit),
+            LogEntry::Killed { x, y } => write!(f, "Genestealer at ({}, {}) is killed", x, y),
+            LogEntry::Missed { x, y } => write!(f, "Genestealer at ({}, {}) survives", x, y),
+            LogEntry::BlipRevealed { unit, x, y, stealers } => write!(f, "{} reveals a blip at ({}, {}): {} genestealer(s)", unit, x, y, stealers),
+            LogEntry::TurnStarted { turn, cps } => write!(f, "Turn {} begins, {} command points", turn, cps),
+            LogEntry::Undone => write!(f, "Last action undone"),
+            LogEntry::Redone => write!(f, "Action redone"),
+            LogEntry::Info(s) => write!(f, "{}", s),
+        }
+    }
+}
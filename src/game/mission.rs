@@ -0,0 +1,50 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+use super::position;
+use super::world;
+
+/// A terminator and where it starts
+#[derive(Deserialize)]
+pub struct Deployment {
+    pub name: String,
+    pub pos: position::Position,
+    #[serde(default = "world::default_weapon")]
+    pub weapon: String,
+    #[serde(default)]
+    pub weapon_kind: world::WeaponKind,
+}
+
+/// Where a blip starts
+#[derive(Deserialize)]
+pub struct BlipDeployment {
+    pub x: u32,
+    pub y: u32,
+}
+
+/// A map and its starting forces, read from a JSON mission file
+#[derive(Deserialize)]
+pub struct Mission {
+    pub name: String,
+    /// One string per row, spaces being walls
+    pub board: Vec<String>,
+    pub terminators: Vec<Deployment>,
+    #[serde(default)]
+    pub blips: Vec<BlipDeployment>,
+    /// Entry areas, objectives and other squares worth naming
+    #[serde(default)]
+    pub labels: Vec<world::Label>,
+}
+
+impl Mission {
+    pub fn read(path: &Path) -> Result<Mission, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// The board as expected by `Board::from_string`
+    pub fn get_board_desc(&self) -> String {
+        self.board.join("\n")
+    }
+}
@@ -1,5 +1,15 @@
+#[allow(clippy::module_inception)]
 pub mod game;
-mod world;
-mod handlers;
-mod handler_move_terminator;
-mod position;
+pub mod world;
+pub mod handlers;
+pub mod handler_move_terminator;
+pub mod handler_replay;
+pub mod position;
+pub mod commands;
+pub mod combat;
+pub mod dice;
+pub mod replay;
+pub mod input;
+pub mod mission;
+pub mod status;
+pub mod log_entry;
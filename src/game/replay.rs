@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::world;
+use super::commands;
+
+pub const REPLAY_DIR: &str = "replays";
+
+/// A game as played: the world at start, then every step taken from there.
+/// Files hold the world on the first line, then one step per line, so steps can be appended as they are taken.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub start: world::World,
+    pub steps: Vec<commands::Step>,
+}
+
+impl Replay {
+    pub fn read(path: &Path) -> Result<Replay, Box<dyn std::error::Error>> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let start = serde_json::from_str(&lines.next().ok_or("empty replay file")??)?;
+        let mut steps = vec![];
+        for line in lines {
+            steps.push(serde_json::from_str(&line?)?);
+        }
+        Ok(Replay{start, steps})
+    }
+
+    /// Write to a new file at `path`, or at `path` with `-2`, `-3`... added to its name if taken, returning where
+    pub fn write(&self, path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let (file, path) = create_new(path)?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer(&mut writer, &self.start)?;
+        writeln!(writer)?;
+        write_steps(writer, &self.steps)?;
+        Ok(path)
+    }
+
+    /// Add `steps` at the end of the replay file at `path`
+    pub fn append(path: &Path, steps: &[commands::Step]) -> Result<(), Box<dyn std::error::Error>> {
+        let file = OpenOptions::new().append(true).open(path)?;
+        write_steps(BufWriter::new(file), steps)
+    }
+
+    /// Rebuild the world as it was after the first `count` steps
+    pub fn world_at(&self, count: usize) -> (world::World, commands::CommandLog) {
+        let mut world = self.start.clone();
+        let mut log = commands::CommandLog::new();
+        for step in self.steps.iter().take(count) {
+            log.apply_step(&mut world, step);
+        }
+        (world, log)
+    }
+
+    /// Index of the first step played in each turn
+    pub fn turn_starts(&self) -> Vec<usize> {
+        let mut world = self.start.clone();
+        let mut log = commands::CommandLog::new();
+        let mut starts = vec![0];
+        for (i, step) in self.steps.iter().enumerate() {
+            let turn = world.turn;
+            log.apply_step(&mut world, step);
+            if world.turn != turn {
+                starts.push(i + 1);
+            }
+        }
+        starts
+    }
+}
+
+/// Create a file at `path` or at the first free numbered variant of it, never replacing an existing one
+fn create_new(path: &Path) -> io::Result<(File, PathBuf)> {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut candidate = path.to_path_buf();
+    for n in 2.. {
+        match OpenOptions::new().write(true).create_new(true).open(&candidate) {
+            Ok(file) => return Ok((file, candidate)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                candidate = path.with_file_name(format!("{}-{}{}", stem, n, extension));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+fn write_steps(mut writer: impl Write, steps: &[commands::Step]) -> Result<(), Box<dyn std::error::Error>> {
+    for step in steps {
+        serde_json::to_writer(&mut writer, step)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// A fresh file name under `REPLAY_DIR` for a game starting now
+pub fn new_replay_path() -> PathBuf {
+    let name = chrono::Local::now().format("shulk-%Y%m%d-%H%M%S.replay").to_string();
+    Path::new(REPLAY_DIR).join(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::position::Direction;
+
+    fn replay() -> Replay {
+        let start = world::World::for_test(&["EEEE", "EEEE"], &[(0, 0, Direction::Right)]);
+        let steps = vec![
+            commands::Step::Execute(commands::Command::Move{id:0, dx:1, dy:0}),
+            commands::Step::Execute(commands::Command::EndTurn),
+            commands::Step::Execute(commands::Command::Move{id:0, dx:1, dy:0}),
+            commands::Step::Undo,
+            commands::Step::Redo,
+            commands::Step::Execute(commands::Command::EndTurn),
+        ];
+        Replay{start, steps}
+    }
+
+    #[test]
+    fn rebuilds_the_world_after_some_steps() {
+        let replay = replay();
+        let (world, _) = replay.world_at(0);
+        assert_eq!((world.terminators[0].pos.x, world.turn), (0, replay.start.turn));
+        let (world, log) = replay.world_at(4);
+        assert_eq!((world.terminators[0].pos.x, world.turn), (1, replay.start.turn + 1));
+        assert_eq!(log.steps().len(), 4);
+        let (world, _) = replay.world_at(100);
+        assert_eq!((world.terminators[0].pos.x, world.turn), (2, replay.start.turn + 2));
+    }
+
+    #[test]
+    fn finds_where_turns_start() {
+        assert_eq!(replay().turn_starts(), vec![0, 2, 6]);
+    }
+
+    #[test]
+    fn appended_steps_are_read_back() {
+        let replay = replay();
+        let path = std::env::temp_dir().join(format!("shulk-test-{}.replay", std::process::id()));
+        assert_eq!(Replay{start:replay.start.clone(), steps:replay.steps[..2].to_vec()}.write(&path).unwrap(), path);
+        Replay::append(&path, &replay.steps[2..]).unwrap();
+        let read = Replay::read(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap().turn_starts(), replay.turn_starts());
+    }
+
+    #[test]
+    fn never_replaces_an_existing_replay() {
+        let dir = std::env::temp_dir().join(format!("shulk-test-replays-{}", std::process::id()));
+        let path = dir.join("game.replay");
+        let replay = replay();
+        let written: Vec<PathBuf> = (0..3).map(|_| replay.write(&path).unwrap()).collect();
+        let names: Vec<String> = written.iter().map(|p| p.file_name().unwrap().to_string_lossy().into_owned()).collect();
+        let steps: Vec<usize> = written.iter().map(|p| Replay::read(p).unwrap().steps.len()).collect();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(names, vec!["game.replay", "game-2.replay", "game-3.replay"]);
+        assert_eq!(steps, vec![replay.steps.len(); 3]);
+    }
+}
@@ -0,0 +1,36 @@
+use super::world;
+use super::commands;
+
+/// The selected unit, as shown next to the board
+pub struct UnitStatus {
+    pub name: String,
+    pub weapon: String,
+    pub aps: u32,
+    pub overwatch: bool,
+    pub jammed: bool,
+}
+
+impl UnitStatus {
+    pub fn from_world(world: &world::World, id: u32) -> Option<UnitStatus> {
+        world.get_terminator(id).map(|t| UnitStatus{
+            name: t.name.clone(),
+            weapon: t.weapon.clone(),
+            aps: t.aps,
+            overwatch: t.overwatch,
+            jammed: t.jammed,
+        })
+    }
+}
+
+/// Where the game stands, as shown next to the board
+pub struct TurnStatus {
+    pub turn: u32,
+    pub cps: u32,
+    pub can_undo: bool,
+}
+
+impl TurnStatus {
+    pub fn from_world(world: &world::World, log: &commands::CommandLog) -> TurnStatus {
+        TurnStatus{turn:world.turn, cps:world.cps, can_undo:log.can_undo()}
+    }
+}
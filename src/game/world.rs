@@ -1,28 +1,36 @@
 
 use array2d::Array2D;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
 
 use crate::graphics;
 use super::position;
+use super::dice;
+use super::mission;
+use super::combat;
+use super::log_entry::LogEntry;
 
 // Board
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 enum Tile {
     Empty,
     Inaccessible
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Board {
     tiles: Array2D<Tile>,
 }
 
 impl Board {
+    /// One row per line, spaces being walls. Rows are measured in characters, short ones ending with walls.
     pub fn from_string(desc: &str) -> Board {
+        let rows: Vec<&str> = desc.split('\n').collect();
+        let w = rows.iter().map(|r| r.chars().count()).max().unwrap_or(0);
         let mut tiles = Vec::<Tile>::new();
-        let (mut w, mut h) = (0, 0);
-        for s in desc.split('\n') {
-            w += 1;
-            h = s.len();
-            for c in s.chars() {
+        for row in rows.iter() {
+            for c in row.chars().chain(std::iter::repeat(' ')).take(w) {
                 if c == ' ' {
                     tiles.push(Tile::Inaccessible{});
                 }
@@ -31,7 +39,7 @@ impl Board {
                 }
             }
         }
-        Board{tiles:Array2D::from_row_major(&tiles, w, h)}
+        Board{tiles:Array2D::from_row_major(&tiles, rows.len(), w)}
     }
 
     pub fn get_visuals(&self) -> Vec<graphics::Visual> {
@@ -42,12 +50,18 @@ impl Board {
                     Tile::Empty => 1,
                     Tile::Inaccessible => 0,
                 };
-                visuals.push(graphics::Visual{content, x:x as u32, y:y as u32, id:None});
+                visuals.push(graphics::Visual{content, x:x as u32, y:y as u32, id:None, color:graphics::Color::Default,
+                    layer:graphics::Layer::Floor});
             }
         }
         visuals
     }
 
+    /// Columns and rows
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.tiles.num_columns() as u32, self.tiles.num_rows() as u32)
+    }
+
     pub fn is_accessible(&self, x:u32, y:u32) -> bool {
         let tile = self.tiles.get(y as usize, x as usize);
         match tile {
@@ -59,14 +73,51 @@ impl Board {
 }
 
 // Terminators
+pub const ACTION_POINTS: u32 = 50;
+const DIRECTIONS: [position::Direction; 4] = [position::Direction::Up, position::Direction::Right, position::Direction::Down, position::Direction::Left];
+pub const STORM_BOLTER: &str = "Storm bolter";
+/// Square and facing of a terminator while planning its moves
+type MoveState = (u32, u32, position::Direction);
+/// Action points, turns and backward moves spent on the way
+type MoveCost = (u32, u32, u32);
+
+/// Weapon of terminators deployed or saved without one
+pub fn default_weapon() -> String {
+    STORM_BOLTER.to_string()
+}
+
+/// How a weapon fires, whatever its name
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum WeaponKind {
+    #[default]
+    Gun,
+    Flamer,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Terminator {
     pub name: String,
     pub id: u32,
     pub pos: position::Position,
     pub aps: u32,
+    #[serde(default = "default_weapon")]
+    pub weapon: String,
+    #[serde(default)]
+    pub weapon_kind: WeaponKind,
+    #[serde(default)]
+    pub overwatch: bool,
+    #[serde(default)]
+    pub jammed: bool,
+    #[serde(default)]
+    pub squad: graphics::Color,
 }
 
 impl Terminator {
+    pub fn new(name: &str, id: u32, pos: position::Position, weapon: &str, weapon_kind: WeaponKind) -> Terminator {
+        Terminator{name:name.to_string(), id, pos, aps:ACTION_POINTS, weapon:weapon.to_string(), weapon_kind, overwatch:false,
+            jammed:false, squad:get_squad(name)}
+    }
+
     pub fn get_visual(&self) -> graphics::Visual {
         let content = match self.pos.dir {
             position::Direction::Up => 2,
@@ -74,7 +125,61 @@ impl Terminator {
             position::Direction::Right => 4,
             position::Direction::Left => 5,
         };
-        graphics::Visual{content, x:self.pos.x, y:self.pos.y, id:Some(self.id)}
+        graphics::Visual{content, x:self.pos.x, y:self.pos.y, id:Some(self.id), color:self.squad, layer:graphics::Layer::Units}
+    }
+
+    /// Name, action points and status, one per line of its annotation
+    pub fn get_details(&self) -> Vec<String> {
+        let mut details = vec![self.name.clone(), format!("{} AP", self.aps)];
+        if self.jammed {
+            details.push("Jammed".to_string());
+        }
+        if self.overwatch {
+            details.push("Overwatch".to_string());
+        }
+        details
+    }
+}
+
+// Genestealers
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stealer {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Stealer {
+    pub fn get_visual(&self) -> graphics::Visual {
+        graphics::Visual{content:6, x:self.x, y:self.y, id:None, color:graphics::Color::Default, layer:graphics::Layer::Units}
+    }
+}
+
+/// Unknown number of genestealers, revealed when seen
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Blip {
+    pub x: u32,
+    pub y: u32,
+    pub stealers: u32,
+}
+
+impl Blip {
+    pub fn get_visual(&self) -> graphics::Visual {
+        graphics::Visual{content:7, x:self.x, y:self.y, id:None, color:graphics::Color::Default, layer:graphics::Layer::Units}
+    }
+}
+
+/// Squad color of the named terminators listed below
+fn get_squad(name: &str) -> graphics::Color {
+    match name {
+        "Brother Zael" | "Brother Omnio" => graphics::Color::Purple,
+        "Brother Claudio" | "Brother Goriel" => graphics::Color::Yellow,
+        "Lexicanium Calistarius" | "Brother Scipio" => graphics::Color::Grey,
+        "Sergeant Lorenzo" | "Brother Deino" | "Sergeant Zaltys" => graphics::Color::Blue,
+        "Sergeant Gideon" | "Brother Noctis" => graphics::Color::Green,
+        "Brother Leon" | "Brother Valencio" => graphics::Color::Red,
+        "Brother Adron" | "Brother Raphael" => graphics::Color::Orange,
+        "Chaplain Raziel" | "Brother Metraen" => graphics::Color::Black,
+        _ => graphics::Color::Default,
     }
 }
 
@@ -117,14 +222,35 @@ Sergeant Arbalan (yellow)
 Brother Gabriel (yellow)
 */
 
+/// Text written beside a square of the board, e.g. an entry area or an objective
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Label {
+    pub x: u32,
+    pub y: u32,
+    pub text: String,
+}
+
 // World
+#[derive(Clone, Serialize, Deserialize)]
 pub struct World {
+    #[serde(default)]
+    pub name: String,
     pub board: Board,
     pub terminators: Vec<Terminator>,
+    #[serde(default)]
+    pub stealers: Vec<Stealer>,
+    #[serde(default)]
+    pub blips: Vec<Blip>,
+    pub turn: u32,
+    #[serde(default)]
+    pub cps: u32,
+    pub dice: dice::Dice,
+    #[serde(default)]
+    pub labels: Vec<Label>,
 }
 
 impl World {
-    pub fn new() -> World {
+    pub fn new(seed: u64) -> World {
         let board_desc =
 "                   EEE     
                    EEE     
@@ -151,29 +277,336 @@ SSSSSDEEEDEEEEEEEEEEEEEEEEE
            I               ";
         let board = Board::from_string(board_desc);
         let terminators = vec![
-            Terminator{name:"Brother Omnio".to_string(), id:0, pos:position::Position{x:0, y:11, dir:position::Direction::Right}, aps:50},
-            Terminator{name:"Brother Claudio".to_string(), id:1, pos:position::Position{x:1, y:11, dir:position::Direction::Right}, aps:50},
-            Terminator{name:"Brother Goriel".to_string(), id:2, pos:position::Position{x:2, y:11, dir:position::Direction::Right}, aps:50},
-            Terminator{name:"Brother Zael".to_string(), id:3, pos:position::Position{x:3, y:11, dir:position::Direction::Right}, aps:50},
-            Terminator{name:"Sergeant Lorenzo".to_string(), id:4, pos:position::Position{x:4, y:11, dir:position::Direction::Right}, aps:50},
+            Terminator::new("Brother Omnio", 0, position::Position{x:0, y:11, dir:position::Direction::Right}, STORM_BOLTER, WeaponKind::Gun),
+            Terminator::new("Brother Claudio", 1, position::Position{x:1, y:11, dir:position::Direction::Right}, STORM_BOLTER, WeaponKind::Gun),
+            Terminator::new("Brother Goriel", 2, position::Position{x:2, y:11, dir:position::Direction::Right}, STORM_BOLTER, WeaponKind::Gun),
+            Terminator::new("Brother Zael", 3, position::Position{x:3, y:11, dir:position::Direction::Right}, STORM_BOLTER, WeaponKind::Gun),
+            Terminator::new("Sergeant Lorenzo", 4, position::Position{x:4, y:11, dir:position::Direction::Right}, STORM_BOLTER, WeaponKind::Gun),
         ];
-        World{board:board, terminators:terminators}
+        let blips = vec![(26, 6), (26, 12), (22, 18), (11, 22)];
+        let mut labels = vec![Label{x:0, y:11, text:"Start".to_string()}];
+        for (x, y) in [(26, 6), (26, 12), (11, 18), (18, 18), (22, 18), (11, 22)].iter() {
+            labels.push(Label{x:*x, y:*y, text:"Entry".to_string()});
+        }
+        World::start("Space Hulk", board, terminators, &blips, labels, seed)
+    }
+
+    pub fn from_mission(mission: &mission::Mission, seed: u64) -> Result<World, String> {
+        let board = Board::from_string(&mission.get_board_desc());
+        let mut terminators = Vec::<Terminator>::new();
+        for (i, d) in mission.terminators.iter().enumerate() {
+            if !board.is_accessible(d.pos.x, d.pos.y) {
+                return Err(format!("{} starts on a wall at ({}, {})", d.name, d.pos.x, d.pos.y));
+            }
+            terminators.push(Terminator::new(&d.name, i as u32, d.pos.clone(), &d.weapon, d.weapon_kind));
+        }
+        let mut blips = Vec::<(u32, u32)>::new();
+        for b in mission.blips.iter() {
+            if !board.is_accessible(b.x, b.y) {
+                return Err(format!("Blip on a wall at ({}, {})", b.x, b.y));
+            }
+            blips.push((b.x, b.y));
+        }
+        Ok(World::start(&mission.name, board, terminators, &blips, mission.labels.clone(), seed))
+    }
+
+    /// First turn, blips filled and command points rolled
+    fn start(name: &str, board: Board, terminators: Vec<Terminator>, blips: &[(u32, u32)], labels: Vec<Label>, seed: u64) -> World {
+        let mut dice = dice::Dice::new(seed);
+        let blips = blips.iter().map(|(x, y)| Blip{x:*x, y:*y, stealers:dice.roll().div_ceil(2)}).collect();
+        let cps = dice.roll();
+        World{name:name.to_string(), board, terminators, stealers:vec![], blips, turn:1, cps, dice,
+            labels}
     }
 
     pub fn get_simple_visuals(&self) -> Vec<graphics::Visual> {
         let mut visuals = self.board.get_visuals();
+        for b in &self.blips {
+            visuals.push(b.get_visual());
+        }
+        for s in &self.stealers {
+            visuals.push(s.get_visual());
+        }
         for t in &self.terminators {
             visuals.push(t.get_visual());
         }
         visuals
     }
 
+    /// Shading of the squares covered by overwatch, then of those the `selected` terminator sees and can shoot,
+    /// and marks on the blips it sees
+    pub fn get_fire_visuals(&self, selected: Option<u32>) -> Vec<graphics::Visual> {
+        let overlay = |content: u32, (x, y): (u32, u32), layer: graphics::Layer| {
+            graphics::Visual{content, x, y, id:None, color:graphics::Color::Default, layer}
+        };
+        let mut visuals = Vec::<graphics::Visual>::new();
+        for t in self.terminators.iter().filter(|t| t.overwatch) {
+            visuals.extend(combat::get_line_of_sight(self, &t.pos).into_iter().map(|s| overlay(9, s, graphics::Layer::Overlay)));
+        }
+        if let Some(t) = selected.and_then(|id| self.get_terminator(id)) {
+            for (x, y) in combat::get_line_of_sight(self, &t.pos) {
+                visuals.push(overlay(8, (x, y), graphics::Layer::Overlay));
+                if self.blips.iter().any(|b| b.x == x && b.y == y) {
+                    visuals.push(overlay(10, (x, y), graphics::Layer::Cursor));
+                }
+            }
+        }
+        visuals
+    }
+
+    /// Action points the terminator `id` needs to reach each square it can with at most `budget`
+    pub fn get_reachable(&self, id: u32, budget: u32) -> Vec<(u32, u32, u32)> {
+        let mut squares = HashMap::<(u32, u32), u32>::new();
+        for ((x, y, _), ((aps, _, _), _)) in self.search_moves(id, budget) {
+            let cost = squares.entry((x, y)).or_insert(aps);
+            *cost = (*cost).min(aps);
+        }
+        let mut squares: Vec<(u32, u32, u32)> = squares.into_iter().map(|((x, y), aps)| (x, y, aps)).collect();
+        squares.sort_unstable();
+        squares
+    }
+
+    /// Positions of the terminator `id` after each move and turn of its cheapest way to (`x`, `y`),
+    /// with at most `budget` action points, as few turns then backward moves as possible
+    pub fn get_path(&self, id: u32, x: u32, y: u32, budget: u32) -> Option<Vec<position::Position>> {
+        let states = self.search_moves(id, budget);
+        let mut state = states.iter()
+            .filter(|((sx, sy, _), _)| *sx == x && *sy == y)
+            .min_by_key(|(_, (cost, _))| *cost)
+            .map(|(state, _)| *state)?;
+        let mut path = vec![];
+        loop {
+            path.push(position::Position{x:state.0, y:state.1, dir:state.2});
+            match states.get(&state).and_then(|(_, previous)| *previous) {
+                Some(previous) => state = previous,
+                None => break,
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Fewest action points, then turns, then backward moves to reach each square and facing, and the state before
+    fn search_moves(&self, id: u32, budget: u32) -> HashMap<MoveState, (MoveCost, Option<MoveState>)> {
+        let mut states = HashMap::new();
+        let t = match self.get_terminator(id) {
+            Some(t) => t,
+            None => return states,
+        };
+        let start = (t.pos.x, t.pos.y, t.pos.dir);
+        let index = |d: position::Direction| DIRECTIONS.iter().position(|o| *o == d).unwrap_or(0);
+        let mut open = BinaryHeap::new();
+        states.insert(start, ((0, 0, 0), None));
+        open.push(Reverse(((0, 0, 0), start.0, start.1, index(start.2))));
+        while let Some(Reverse((cost, x, y, dir))) = open.pop() {
+            let dir = DIRECTIONS[dir];
+            if states.get(&(x, y, dir)).map(|(c, _)| *c) != Some(cost) {
+                continue;
+            }
+            let (aps, turns, backs) = cost;
+            let mut next = vec![
+                ((x, y, dir.rotate_left()), (aps, turns + 1, backs)),
+                ((x, y, dir.rotate_right()), (aps, turns + 1, backs)),
+            ];
+            if aps < budget {
+                for (back, (dx, dy)) in [(0, dir.move_frontward()), (1, dir.move_backward())].iter() {
+                    let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                    if nx < 0 || ny < 0 {
+                        continue;
+                    }
+                    let (nx, ny) = (nx as u32, ny as u32);
+                    let free = (nx, ny) == (start.0, start.1) || !self.is_occupied(nx, ny);
+                    if self.board.is_accessible(nx, ny) && free {
+                        next.push(((nx, ny, dir), (aps + 1, turns, backs + back)));
+                    }
+                }
+            }
+            for (state, cost) in next {
+                if states.get(&state).is_none_or(|(c, _)| cost < *c) {
+                    states.insert(state, (cost, Some((x, y, dir))));
+                    open.push(Reverse((cost, state.0, state.1, index(state.2))));
+                }
+            }
+        }
+        states
+    }
+
+    /// Shading of the squares the terminator `id` can reach with its action points, then with command points added,
+    /// and the path to `destination` if the action points reach it, arrows showing the facing on each square
+    pub fn get_move_visuals(&self, id: u32, destination: Option<(u32, u32)>) -> Vec<graphics::Visual> {
+        let visual = |content: u32, x: u32, y: u32, layer: graphics::Layer| {
+            graphics::Visual{content, x, y, id:None, color:graphics::Color::Default, layer}
+        };
+        let aps = match self.get_terminator(id) {
+            Some(t) => t.aps,
+            None => return vec![],
+        };
+        let mut visuals = Vec::<graphics::Visual>::new();
+        for (x, y, cost) in self.get_reachable(id, aps + self.cps) {
+            if cost > 0 {
+                visuals.push(visual(if cost <= aps { 11 } else { 12 }, x, y, graphics::Layer::Overlay));
+            }
+        }
+        // Command points cannot be spent on planned moves, so no path to the squares they reach
+        if let Some((x, y)) = destination {
+            match self.get_path(id, x, y, aps) {
+                Some(path) => {
+                    // The facing leaving each square, after any turn there
+                    for (i, pos) in path.iter().enumerate() {
+                        let leaving = path.get(i + 1).is_none_or(|next| (next.x, next.y) != (pos.x, pos.y));
+                        if !leaving || (pos.x, pos.y) == (path[0].x, path[0].y) {
+                            continue;
+                        }
+                        let content = match pos.dir {
+                            position::Direction::Up => 13,
+                            position::Direction::Down => 14,
+                            position::Direction::Right => 15,
+                            position::Direction::Left => 16,
+                        };
+                        visuals.push(visual(content, pos.x, pos.y, graphics::Layer::Cursor));
+                    }
+                }
+                None => visuals.push(visual(10, x, y, graphics::Layer::Cursor)),
+            }
+        }
+        visuals
+    }
+
+    pub fn get_terminator(&self, id: u32) -> Option<&Terminator> {
+        self.terminators.iter().find(|t| t.id == id)
+    }
+
+    pub fn get_terminator_mut(&mut self, id: u32) -> Option<&mut Terminator> {
+        self.terminators.iter_mut().find(|t| t.id == id)
+    }
+
+    pub fn get_terminator_at(&self, x: u32, y: u32) -> Option<&Terminator> {
+        self.terminators.iter().find(|t| t.pos.x == x && t.pos.y == y)
+    }
+
+    pub fn is_occupied(&self, x: u32, y: u32) -> bool {
+        self.get_terminator_at(x, y).is_some()
+            || self.stealers.iter().any(|s| s.x == x && s.y == y)
+            || self.blips.iter().any(|b| b.x == x && b.y == y)
+    }
+
+    pub fn end_turn(&mut self) -> LogEntry {
+        self.turn += 1;
+        self.cps = self.dice.roll();
+        for t in self.terminators.iter_mut() {
+            t.aps = ACTION_POINTS;
+            t.overwatch = false;
+        }
+        LogEntry::TurnStarted{turn:self.turn, cps:self.cps}
+    }
+
     pub fn can_move(&self, pos: &position::Position, dx: i32, dy: i32) -> bool {
         let x = pos.x as i32 + dx;
         let y = pos.y as i32 + dy;
         if x < 0 || y < 0 {
             return false;
         }
-        self.board.is_accessible(x as u32, y as u32)
+        self.board.is_accessible(x as u32, y as u32) && !self.is_occupied(x as u32, y as u32)
+    }
+}
+
+#[cfg(test)]
+impl World {
+    /// A small world for tests: `rows` as the board, one storm bolter terminator per position
+    pub fn for_test(rows: &[&str], positions: &[(u32, u32, position::Direction)]) -> World {
+        let terminators = positions.iter().enumerate().map(|(i, (x, y, dir))| mission::Deployment{
+            name:format!("Brother {}", i), pos:position::Position{x:*x, y:*y, dir:*dir}, weapon:default_weapon(),
+            weapon_kind:WeaponKind::Gun,
+        }).collect();
+        let mission = mission::Mission{name:"Test".to_string(), board:rows.iter().map(|r| r.to_string()).collect(),
+            terminators, blips:vec![], labels:vec![]};
+        World::from_mission(&mission, 1).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn board_rows_are_measured_in_characters() {
+        let board = Board::from_string("EÉE\nE");
+        assert_eq!(board.get_size(), (3, 2));
+        assert!(board.is_accessible(2, 0));
+        assert!(board.is_accessible(0, 1) && !board.is_accessible(1, 1) && !board.is_accessible(2, 1));
+    }
+
+    #[test]
+    fn reads_saves_without_later_fields() {
+        let mut saved = serde_json::to_value(World::new(3)).unwrap();
+        for field in &["name", "stealers", "blips", "cps", "labels"] {
+            saved.as_object_mut().unwrap().remove(*field);
+        }
+        for t in saved["terminators"].as_array_mut().unwrap() {
+            for field in &["weapon", "weapon_kind", "overwatch", "jammed", "squad"] {
+                t.as_object_mut().unwrap().remove(*field);
+            }
+        }
+        let world: World = serde_json::from_value(saved).unwrap();
+        assert!(world.stealers.is_empty() && world.blips.is_empty() && world.labels.is_empty());
+        assert_eq!(world.cps, 0);
+        let t = world.get_terminator(0).unwrap();
+        assert_eq!((t.weapon.as_str(), t.weapon_kind), (STORM_BOLTER, WeaponKind::Gun));
+        assert!(!t.overwatch && !t.jammed);
+    }
+
+    /// Squares and facings along the path of terminator 0 to (`x`, `y`)
+    fn path(world: &World, x: u32, y: u32, budget: u32) -> Option<Vec<(u32, u32, position::Direction)>> {
+        world.get_path(0, x, y, budget).map(|path| path.iter().map(|p| (p.x, p.y, p.dir)).collect())
+    }
+
+    #[test]
+    fn moves_cost_action_points_and_turns_are_free() {
+        let world = World::for_test(&["EEEE", "EEEE"], &[(1, 0, position::Direction::Right)]);
+        let reachable = world.get_reachable(0, 2);
+        for square in &[(1, 0, 0), (2, 0, 1), (3, 0, 2), (0, 0, 1), (1, 1, 1), (0, 1, 2), (2, 1, 2)] {
+            assert!(reachable.contains(square), "{:?} not in {:?}", square, reachable);
+        }
+        assert!(!reachable.iter().any(|(x, y, _)| (*x, *y) == (3, 1)));
+    }
+
+    #[test]
+    fn paths_step_back_rather_than_turn() {
+        use position::Direction::*;
+        let world = World::for_test(&["EEEE", "EEEE"], &[(1, 0, Right)]);
+        assert_eq!(path(&world, 0, 0, 1), Some(vec![(1, 0, Right), (0, 0, Right)]));
+        assert_eq!(path(&world, 1, 1, 1), Some(vec![(1, 0, Right), (1, 0, Down), (1, 1, Down)]));
+        assert_eq!(path(&world, 1, 0, 0), Some(vec![(1, 0, Right)]));
+        assert_eq!(path(&world, 3, 0, 1), None);
+    }
+
+    #[test]
+    fn paths_go_around_walls_and_models() {
+        let world = World::for_test(&["EE E", "EEEE"], &[(0, 0, position::Direction::Right), (0, 1, position::Direction::Right)]);
+        let reachable = world.get_reachable(0, 10);
+        assert!(!reachable.iter().any(|(x, y, _)| (*x, *y) == (2, 0) || (*x, *y) == (0, 1)));
+        assert!(reachable.contains(&(3, 0, 5)));
+        assert_eq!(path(&world, 0, 1, 10), None);
+        assert_eq!(path(&world, 3, 0, 4), None);
+        // Five moves and three turns
+        assert_eq!(path(&world, 3, 0, 5).unwrap().len(), 9);
+    }
+
+    #[test]
+    fn command_points_shade_squares_without_a_path() {
+        let mut world = World::for_test(&["EEEE"], &[(0, 0, position::Direction::Right)]);
+        world.cps = 2;
+        world.get_terminator_mut(0).unwrap().aps = 1;
+        let shading = |visuals: &[graphics::Visual]| -> Vec<(u32, u32, u32)> {
+            visuals.iter().filter(|v| v.layer == graphics::Layer::Overlay).map(|v| (v.content, v.x, v.y)).collect()
+        };
+        let cursors = |visuals: &[graphics::Visual]| -> Vec<(u32, u32, u32)> {
+            visuals.iter().filter(|v| v.layer == graphics::Layer::Cursor).map(|v| (v.content, v.x, v.y)).collect()
+        };
+        let visuals = world.get_move_visuals(0, Some((1, 0)));
+        assert_eq!(shading(&visuals), vec![(11, 1, 0), (12, 2, 0), (12, 3, 0)]);
+        assert_eq!(cursors(&visuals), vec![(15, 1, 0)]);
+        let visuals = world.get_move_visuals(0, Some((3, 0)));
+        assert_eq!(cursors(&visuals), vec![(10, 3, 0)]);
     }
 }
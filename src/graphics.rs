@@ -1,12 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// Colors of the squads, `Default` leaving the choice to the renderer
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Color {
+    #[default]
+    Default,
+    Purple,
+    Yellow,
+    Grey,
+    Blue,
+    Green,
+    Red,
+    Orange,
+    Black,
+}
+
+/// What a visual is, visuals of a same square being drawn from the lowest layer up
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Layer {
+    /// Tiles of the board
+    Floor,
+    /// Shading of the squares under, e.g. field of fire, which can be hidden
+    Overlay,
+    Units,
+    /// Highlight of the squares under, with marks such as planned paths
+    Cursor,
+    /// Short-lived glyphs over everything, e.g. muzzle flashes
+    Effects,
+}
 
 pub struct Visual {
     pub content: u32,
     pub x: u32,
     pub y: u32,
     pub id: Option<u32>,
+    pub color: Color,
+    pub layer: Layer,
 }
 
 pub enum Text {
+    /// Line above the board, e.g. mission name and turn
     Title(String),
     ItemAnnotation(u32, String),
+    /// Box of several lines connected to an item, e.g. name, AP and status
+    ItemDetails(u32, Vec<String>),
+    /// Short-lived text floating over a square, e.g. dice rolls
+    Popup(u32, u32, String),
+    /// Text written beside a square, e.g. entry areas and objectives
+    TileLabel(u32, u32, String),
+}
+
+/// Something that just happened on the board, for the renderer to animate
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    /// A model going from a square to the next
+    Slide { content: u32, color: Color, from: (u32, u32), to: (u32, u32) },
+    /// Muzzle flashes along the squares of a line of fire, nearest first
+    Fire { squares: Vec<(u32, u32)> },
+    /// Flames filling squares, nearest first
+    Flame { squares: Vec<(u32, u32)> },
+    /// A model fading away where it died
+    Fade { content: u32, color: Color, x: u32, y: u32 },
 }
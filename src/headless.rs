@@ -0,0 +1,119 @@
+use crate::events;
+use crate::game::game;
+use crate::game::input::GameInput;
+
+/// Runs a game without a terminal, feeding it scripted inputs. Nothing is written: Save and Load are ignored
+/// and the game is not recorded.
+pub struct Driver {
+    game: game::Game,
+    quit: bool,
+}
+
+impl Driver {
+    pub fn new(mut game: game::Game) -> Driver {
+        game.set_save_path(None);
+        game.set_replay_path(None);
+        Driver{game, quit:false}
+    }
+
+    pub fn send(&mut self, event: events::Event<GameInput>) -> game::GameUpdate {
+        let update = self.game.handle_event(event);
+        self.quit = self.quit || update.quit;
+        update
+    }
+
+    /// Feed events until the script ends or the game quits
+    pub fn run<I>(&mut self, events: I) -> Vec<game::GameUpdate>
+    where I: IntoIterator<Item = events::Event<GameInput>>
+    {
+        let mut updates = vec![];
+        for event in events {
+            if self.quit {
+                break;
+            }
+            updates.push(self.send(event));
+        }
+        updates
+    }
+
+    pub fn has_quit(&self) -> bool {
+        self.quit
+    }
+
+    pub fn game(&self) -> &game::Game {
+        &self.game
+    }
+}
+
+/// Parse a whitespace separated script such as `MoveForward TurnLeft Select:2 tick Quit`
+pub fn parse_script(script: &str) -> Result<Vec<events::Event<GameInput>>, String> {
+    script.split_whitespace().map(|token| match token {
+        "tick" => Ok(events::Event::Tick),
+        _ => token.parse::<GameInput>().map(events::Event::Input),
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::position::Direction;
+    use crate::game::world;
+
+    fn driver() -> Driver {
+        let world = world::World::for_test(&["EEEE", "EEEE"], &[(0, 1, Direction::Right), (0, 0, Direction::Right)]);
+        Driver::new(game::Game::from_world(world))
+    }
+
+    #[test]
+    fn parses_inputs_and_ticks() {
+        let events = parse_script("Select:1 tick\n  Target:2,3 Quit").unwrap();
+        assert_eq!(events.len(), 4);
+        assert!(matches!(events[0], events::Event::Input(GameInput::Select(1))));
+        assert!(matches!(events[1], events::Event::Tick));
+        assert!(matches!(events[2], events::Event::Input(GameInput::Target(2, 3))));
+        assert!(parse_script("MoveForward Fly").is_err());
+    }
+
+    #[test]
+    fn plays_until_quit() {
+        let mut driver = driver();
+        let turn = driver.game().world().turn;
+        let script = "Select:0 MoveForward MoveForward Select:1 TurnRight MoveForward EndTurn Quit MoveForward";
+        let updates = driver.run(parse_script(script).unwrap());
+        assert_eq!(updates.len(), 8);
+        assert!(driver.has_quit());
+        let world = driver.game().world();
+        assert_eq!(world.turn, turn + 1);
+        let positions: Vec<(u32, u32)> = world.terminators.iter().map(|t| (t.pos.x, t.pos.y)).collect();
+        assert_eq!(positions, vec![(2, 1), (0, 1)]);
+        assert_eq!(world.terminators[1].pos.dir, Direction::Down);
+    }
+
+    #[test]
+    fn ignores_save_and_load() {
+        let mut driver = driver();
+        let updates = driver.run(parse_script("Save Load").unwrap());
+        let messages: Vec<String> = updates.iter().flat_map(|u| u.log.iter()).map(|e| e.to_string()).collect();
+        assert_eq!(messages, vec!["Saving is disabled", "Loading is disabled"]);
+        assert!(!driver.has_quit());
+    }
+
+    #[test]
+    fn loaded_games_are_not_recorded() {
+        let path = std::env::temp_dir().join(format!("shulk-test-{}.sav", std::process::id()));
+        let mut game = game::Game::from_world(world::World::for_test(&["EEEE"], &[(0, 0, Direction::Right)]));
+        game.set_save_path(Some(path.clone()));
+        game.handle_event(events::Event::Input(GameInput::Save));
+        let loaded = game::Game::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert!(loaded.replay_path().is_none());
+
+        let mut recorded = game::Game::from_world(loaded.world().clone());
+        recorded.set_replay_path(Some(path));
+        let mut driver = Driver::new(recorded);
+        driver.run(parse_script("MoveForward").unwrap());
+        assert!(driver.game().replay_path().is_none());
+        assert_eq!(driver.game().world().terminators[0].pos.x, 1);
+    }
+}
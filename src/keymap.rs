@@ -0,0 +1,311 @@
+
+use crossterm::event::{KeyCode, KeyEvent};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::events;
+use crate::game::input::GameInput;
+
+pub const DEFAULT_KEYMAP_PATH: &str = "keymap.json";
+
+/// Keys used to move and turn the selected unit
+#[derive(Clone, Copy)]
+pub enum Layout {
+    Arrows,
+    Vi,
+    Wasd,
+    Numpad,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        match s {
+            "arrows" => Ok(Layout::Arrows),
+            "vi" => Ok(Layout::Vi),
+            "wasd" => Ok(Layout::Wasd),
+            "numpad" => Ok(Layout::Numpad),
+            _ => Err(format!("unknown layout '{}', expected arrows, vi, wasd or numpad", s)),
+        }
+    }
+}
+
+/// What the keys drive: a game being played or a replay being watched
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Play,
+    Replay,
+}
+
+impl Context {
+    /// Whether `input` does anything in this context
+    pub fn uses(&self, input: GameInput) -> bool {
+        match input {
+            GameInput::Quit | GameInput::ToggleHelp | GameInput::PanUp | GameInput::PanDown | GameInput::PanLeft
+                | GameInput::PanRight | GameInput::Recenter | GameInput::ScrollLogUp | GameInput::ScrollLogDown
+                | GameInput::ToggleOverlays | GameInput::ViewLog | GameInput::Confirm => true,
+            GameInput::TogglePause | GameInput::StepForward | GameInput::StepBack | GameInput::NextTurn
+                | GameInput::PreviousTurn | GameInput::Digit(_) => *self == Context::Replay,
+            _ => *self == Context::Play,
+        }
+    }
+}
+
+/// Content of a keymap file, e.g. `{"layout": "vi", "bindings": {"Undo": ["z"]}}`
+#[derive(Deserialize)]
+struct KeymapFile {
+    layout: Option<String>,
+    #[serde(default)]
+    bindings: BTreeMap<String, Vec<String>>,
+}
+
+/// Turns terminal keys into the game inputs used in its context.
+/// A key may be bound to one input while playing and to another in replays.
+pub struct Keymap {
+    bindings: Vec<(KeyCode, GameInput)>,
+    context: Context,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::new()
+    }
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        Keymap::with_layout(Layout::Arrows)
+    }
+
+    pub fn with_layout(layout: Layout) -> Keymap {
+        let moves = match layout {
+            Layout::Arrows => [KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right],
+            Layout::Vi => [KeyCode::Char('k'), KeyCode::Char('j'), KeyCode::Char('h'), KeyCode::Char('l')],
+            Layout::Wasd => [KeyCode::Char('w'), KeyCode::Char('s'), KeyCode::Char('a'), KeyCode::Char('d')],
+            Layout::Numpad => [KeyCode::Char('8'), KeyCode::Char('2'), KeyCode::Char('4'), KeyCode::Char('6')],
+        };
+        let mut bindings = vec![
+            (moves[0], GameInput::MoveForward),
+            (moves[1], GameInput::MoveBackward),
+            (moves[2], GameInput::TurnLeft),
+            (moves[3], GameInput::TurnRight),
+            (KeyCode::Tab, GameInput::SelectNext),
+            (KeyCode::Char('f'), GameInput::Shoot),
+            (KeyCode::Char('o'), GameInput::Overwatch),
+            (KeyCode::Char('x'), GameInput::ClearJam),
+            (KeyCode::Char('m'), GameInput::PlanMove),
+            (KeyCode::Char('e'), GameInput::EndTurn),
+            (KeyCode::Char('u'), GameInput::Undo),
+            (KeyCode::Char('r'), GameInput::Redo),
+            (KeyCode::F(5), GameInput::Save),
+            (KeyCode::F(9), GameInput::Load),
+            (KeyCode::Char('q'), GameInput::Quit),
+            (KeyCode::Char('?'), GameInput::ToggleHelp),
+            (KeyCode::Char('I'), GameInput::PanUp),
+            (KeyCode::Char('K'), GameInput::PanDown),
+            (KeyCode::Char('J'), GameInput::PanLeft),
+            (KeyCode::Char('L'), GameInput::PanRight),
+            (KeyCode::Char('c'), GameInput::Recenter),
+            (KeyCode::PageUp, GameInput::ScrollLogUp),
+            (KeyCode::PageDown, GameInput::ScrollLogDown),
+            (KeyCode::Char('v'), GameInput::ToggleOverlays),
+            (KeyCode::Char('g'), GameInput::ViewLog),
+            (KeyCode::Char(' '), GameInput::TogglePause),
+            (KeyCode::Char('.'), GameInput::StepForward),
+            (KeyCode::Char(','), GameInput::StepBack),
+            (KeyCode::Char('n'), GameInput::NextTurn),
+            (KeyCode::Char('p'), GameInput::PreviousTurn),
+            (KeyCode::Enter, GameInput::Confirm),
+        ];
+        // Digits only matter in replays, so they can move units with the numpad layout
+        for d in 0..10 {
+            if let Some(c) = std::char::from_digit(d, 10) {
+                bindings.push((KeyCode::Char(c), GameInput::Digit(d)));
+            }
+        }
+        Keymap{bindings, context:Context::Play}
+    }
+
+    pub fn set_context(&mut self, context: Context) {
+        self.context = context;
+    }
+
+    /// Read a keymap file: a layout, then bindings replacing the layout keys of their input
+    pub fn load(path: &Path) -> Result<Keymap, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        Keymap::parse(&content).map_err(|e| format!("invalid keymap {}: {}", path.display(), e))
+    }
+
+    /// Parse the JSON content of a keymap file
+    pub fn parse(content: &str) -> Result<Keymap, String> {
+        let file: KeymapFile = serde_json::from_str(content).map_err(|e| e.to_string())?;
+
+        let mut keymap = match file.layout {
+            Some(layout) => Keymap::with_layout(layout.parse()?),
+            None => Keymap::new(),
+        };
+        for (name, keys) in file.bindings.iter() {
+            let input: GameInput = name.parse()?;
+            let at = keymap.bindings.iter().position(|(_, i)| *i == input).unwrap_or(keymap.bindings.len());
+            keymap.bindings.retain(|(_, i)| *i != input);
+            for (n, key) in keys.iter().enumerate() {
+                keymap.bindings.insert(at + n, (parse_key(key)?, input));
+            }
+        }
+        keymap.check_conflicts()?;
+        Ok(keymap)
+    }
+
+    /// The file at `path` if there is one, the default keymap otherwise
+    pub fn load_or_default(path: &Path) -> Result<Keymap, String> {
+        if path.exists() {
+            Keymap::load(path)
+        }
+        else {
+            Ok(Keymap::new())
+        }
+    }
+
+    /// Keys bound to two inputs used in the same context
+    fn check_conflicts(&self) -> Result<(), String> {
+        for (i, (key, input)) in self.bindings.iter().enumerate() {
+            let conflict = |(k, o): &&(KeyCode, GameInput)| k == key && o != input
+                && [Context::Play, Context::Replay].iter().any(|c| c.uses(*input) && c.uses(*o));
+            if let Some((_, other)) = self.bindings[i+1..].iter().find(conflict) {
+                return Err(format!("key '{}' is bound to both {} and {}", key_name(key), input, other));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_input(&self, key: &KeyEvent) -> Option<GameInput> {
+        self.bindings.iter().find(|(k, i)| *k == key.code && self.context.uses(*i)).map(|(_, i)| *i)
+    }
+
+    /// Unbound keys give `None`
+    pub fn translate(&self, event: events::Event<KeyEvent>) -> Option<events::Event<GameInput>> {
+        match event {
+            events::Event::Input(key) => self.get_input(&key).map(events::Event::Input),
+            events::Event::Tick => Some(events::Event::Tick),
+            events::Event::Resize(w, h) => Some(events::Event::Resize(w, h)),
+        }
+    }
+
+    /// One line per input used in the context, listing its keys, in binding order
+    pub fn describe(&self) -> Vec<String> {
+        let mut inputs: Vec<GameInput> = vec![];
+        for (_, input) in self.bindings.iter().filter(|(_, i)| self.context.uses(*i)) {
+            let input = match input {
+                GameInput::Digit(_) => GameInput::Digit(0),
+                _ => *input,
+            };
+            if !inputs.contains(&input) {
+                inputs.push(input);
+            }
+        }
+        inputs.iter().map(|input| {
+            let keys: Vec<String> = self.bindings.iter()
+                .filter(|(_, i)| i == input || matches!((i, input), (GameInput::Digit(_), GameInput::Digit(_))))
+                .map(|(k, _)| key_name(k))
+                .collect();
+            let name = match input {
+                GameInput::Digit(_) => "Digit".to_string(),
+                _ => input.to_string(),
+            };
+            format!("{:<12} {}", keys.join(" "), name)
+        }).collect()
+    }
+}
+
+pub fn parse_key(name: &str) -> Result<KeyCode, String> {
+    let code = match name {
+        "Up" => KeyCode::Up,
+        "Down" => KeyCode::Down,
+        "Left" => KeyCode::Left,
+        "Right" => KeyCode::Right,
+        "Enter" => KeyCode::Enter,
+        "Tab" => KeyCode::Tab,
+        "Esc" => KeyCode::Esc,
+        "Space" => KeyCode::Char(' '),
+        "Backspace" => KeyCode::Backspace,
+        "Home" => KeyCode::Home,
+        "End" => KeyCode::End,
+        "PageUp" => KeyCode::PageUp,
+        "PageDown" => KeyCode::PageDown,
+        "Insert" => KeyCode::Insert,
+        "Delete" => KeyCode::Delete,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                (Some('F'), Some(_)) => match name[1..].parse::<u8>() {
+                    Ok(n) => KeyCode::F(n),
+                    Err(_) => return Err(format!("unknown key '{}'", name)),
+                },
+                _ => return Err(format!("unknown key '{}'", name)),
+            }
+        }
+    };
+    Ok(code)
+}
+
+pub fn key_name(code: &KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        _ => format!("{:?}", code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn input(keymap: &Keymap, c: char) -> Option<GameInput> {
+        keymap.get_input(&KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn bindings_replace_layout_keys() {
+        let keymap = Keymap::parse(r#"{"layout": "vi", "bindings": {"Undo": ["z", "F2"]}}"#).unwrap();
+        assert_eq!(input(&keymap, 'k'), Some(GameInput::MoveForward));
+        assert_eq!(input(&keymap, 'z'), Some(GameInput::Undo));
+        assert_eq!(input(&keymap, 'u'), None);
+    }
+
+    #[test]
+    fn rejects_bad_keymaps() {
+        let error = Keymap::parse(r#"{"bindings": {"Undo": ["q"]}}"#).err().unwrap();
+        assert!(error.contains("'q' is bound to both"), "{}", error);
+        let error = Keymap::parse(r#"{"bindings": {"Undo": ["Hyper"]}}"#).err().unwrap();
+        assert!(error.contains("unknown key 'Hyper'"), "{}", error);
+        let error = Keymap::parse(r#"{"bindings": {"Fly": ["z"]}}"#).err().unwrap();
+        assert!(error.contains("unknown input 'Fly'"), "{}", error);
+        assert!(Keymap::parse(r#"{"layout": "dvorak"}"#).is_err());
+    }
+
+    #[test]
+    fn keys_can_differ_between_contexts() {
+        let mut keymap = Keymap::with_layout(Layout::Numpad);
+        assert_eq!(input(&keymap, '8'), Some(GameInput::MoveForward));
+        keymap.set_context(Context::Replay);
+        assert_eq!(input(&keymap, '8'), Some(GameInput::Digit(8)));
+        assert!(Keymap::parse(r#"{"bindings": {"StepForward": ["f"]}}"#).is_ok());
+    }
+
+    #[test]
+    fn help_lists_inputs_of_the_context() {
+        let mut keymap = Keymap::new();
+        let help = keymap.describe().join("\n");
+        assert!(help.contains("Shoot") && !help.contains("StepForward") && !help.contains("Digit"));
+        keymap.set_context(Context::Replay);
+        let help = keymap.describe().join("\n");
+        assert!(!help.contains("Shoot") && help.contains("StepForward") && help.contains("Digit"));
+    }
+}
@@ -0,0 +1,6 @@
+pub mod rendering;
+pub mod game;
+pub mod events;
+pub mod graphics;
+pub mod headless;
+pub mod keymap;
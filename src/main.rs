@@ -1,71 +1,118 @@
 
-use crossterm::{
-    event::{self, Event as CEvent, KeyEvent},
-    terminal::{disable_raw_mode, enable_raw_mode},
-};
+use crossterm::event::KeyEvent;
+use std::env;
+use std::fs;
 use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::mpsc;
-use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::Rect,
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style},
     widgets::{
-        Block, BorderType, Borders, Paragraph,
+        Block, BorderType, Borders, Clear, Paragraph, Wrap,
     },
     Terminal,
-    text::Text,
+    text::Spans,
 };
-use log::LevelFilter;
+use log::{info, warn, LevelFilter};
 use log4rs::append::file::FileAppender;
 use log4rs::encode::pattern::PatternEncoder;
 use log4rs::config::{Appender, Config, Root};
 
-mod rendering;
-mod game;
-mod events;
-mod graphics;
-
-fn event_loop(events_tx: mpsc::Sender<events::Event<KeyEvent>>) {
-    let tick_rate = Duration::from_millis(30);
-    thread::spawn(move || {
-        let mut last_tick = Instant::now();
-        loop {
-            let timeout = tick_rate
-                .checked_sub(last_tick.elapsed())
-                .unwrap_or_else(|| Duration::from_secs(0));
-
-            if event::poll(timeout).expect("poll works") {
-                if let CEvent::Key(key) = event::read().expect("can read events") {
-                    events_tx.send(events::Event::Input(key)).expect("can send events");
-                }
-            }
+mod cli;
+mod panels;
+mod terminal;
 
-            if last_tick.elapsed() >= tick_rate {
-                if let Ok(_) = events_tx.send(events::Event::Tick) {
-                    last_tick = Instant::now();
-                }
-            }
-        }
-    });
-}
+use shulk::{events, game, headless, keymap, rendering};
+use shulk::game::input::GameInput;
+
+/// Rows of the log under the board
+const PANEL_HEIGHT: u16 = 10;
+
+/// Log lines moved by one scroll
+const LOG_SCROLL_STEP: usize = 4;
+
+/// Squares moved by one pan
+const PAN_STEP: i32 = 4;
 
 /// Main loop
-fn game_loop<B>(terminal: &mut Terminal<B>, events_rx: mpsc::Receiver<events::Event<KeyEvent>>, mut game: game::game::Game) -> Result<(), Box<dyn std::error::Error>>
+fn game_loop<B>(terminal: &mut Terminal<B>, events_rx: mpsc::Receiver<events::Event<KeyEvent>>, pump: &events::InputPump, keymap: keymap::Keymap,
+                theme: &rendering::theme::Theme, mut game: game::game::Game, log_path: &Path) -> Result<(), Box<dyn std::error::Error>>
 where B : Backend
 {
+    let help = keymap.describe();
+    let mut show_help = false;
+    let mut board = rendering::rendering::BoardState::new();
+    let mut messages = panels::MessageLog::new();
     loop {
-        let event = events_rx.recv()?;
-        let update = (&mut game).handle_event(event);
+        let event = match keymap.translate(events_rx.recv()?) {
+            Some(event) => event,
+            None => continue,
+        };
+        // Inputs but Quit fast forward animations instead of reaching the game
+        match event {
+            events::Event::Input(input) if input != GameInput::Quit && board.animations.is_playing() => {
+                board.animations.skip();
+                continue;
+            }
+            events::Event::Tick => board.animations.tick(),
+            _ => {}
+        }
+        if let events::Event::Input(input) = event {
+            match input {
+                GameInput::ToggleHelp => show_help = !show_help,
+                GameInput::PanUp => board.camera.pan(0, -PAN_STEP),
+                GameInput::PanDown => board.camera.pan(0, PAN_STEP),
+                GameInput::PanLeft => board.camera.pan(-PAN_STEP, 0),
+                GameInput::PanRight => board.camera.pan(PAN_STEP, 0),
+                GameInput::Recenter => board.camera.follow(),
+                GameInput::ScrollLogUp => messages.scroll_up(LOG_SCROLL_STEP),
+                GameInput::ScrollLogDown => messages.scroll_down(LOG_SCROLL_STEP),
+                GameInput::ToggleOverlays => board.overlays = !board.overlays,
+                GameInput::ViewLog => {
+                    view_file(terminal, pump, log_path)?;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        let update = game.handle_event(event);
         if update.quit {
             break;
         }
+        messages.extend(&update.log);
+        board.animations.extend(&update.effects);
 
         terminal.draw(|frame| {
 	    let size = frame.size();
-            let content = rendering::rendering::render(&update.visuals, &update.texts);
-            let board = Paragraph::new(Text::from(content))
+            let rows = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(0), Constraint::Length(PANEL_HEIGHT)])
+                .split(size);
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(0), Constraint::Length(panels::SIDE_WIDTH)])
+                .split(rows[0]);
+            let side = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(panels::UNIT_HEIGHT), Constraint::Min(0)])
+                .split(columns[1]);
+            let board_area = columns[0];
+            let max_w = board_area.width.saturating_sub(2) as u32;
+            let max_h = board_area.height.saturating_sub(2) as u32;
+            let content = match rendering::rendering::render(&update.visuals, &update.texts, theme, &mut board, max_w, max_h, update.selected) {
+                Some(content) => content,
+                None => {
+                    let message = format!("Terminal too small: {}x{} available", size.width, size.height);
+                    frame.render_widget(Paragraph::new(message).wrap(Wrap{trim:true}), size);
+                    return;
+                }
+            };
+
+            let board = Paragraph::new(content)
                 .style(Style::default().fg(Color::LightCyan))
                 .block(
                     Block::default()
@@ -74,7 +121,26 @@ where B : Backend
                         .title("Board")
                         .border_type(BorderType::Plain),
                 );
-            frame.render_widget(board, Rect::new(0, 0, size.width, size.height - 10));
+            frame.render_widget(board, board_area);
+            panels::draw_unit(frame, side[0], update.unit.as_ref());
+            panels::draw_turn(frame, side[1], &update.turn);
+            messages.draw(frame, rows[1]);
+
+            if show_help {
+                let w = help.iter().map(|l| l.len() as u16).max().unwrap_or(0) + 4;
+                let h = help.len() as u16 + 2;
+                let area = Rect::new(size.width.saturating_sub(w) / 2, size.height.saturating_sub(h) / 2, w, h).intersection(size);
+                let lines: Vec<Spans> = help.iter().map(|l| Spans::from(l.as_str())).collect();
+                let overlay = Paragraph::new(lines)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Keys")
+                            .border_type(BorderType::Plain),
+                    );
+                frame.render_widget(Clear, area);
+                frame.render_widget(overlay, area);
+            }
         })?;
 
     }
@@ -83,21 +149,50 @@ where B : Backend
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let logfile = FileAppender::builder()
-	.encoder(Box::new(PatternEncoder::new("{l} - {m}\n")))
-	.build("log/output.log")?;
+    let options = match cli::Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
 
-    let config = Config::builder()
-	.appender(Appender::builder().build("logfile", Box::new(logfile)))
-	.build(Root::builder()
-	    .appender("logfile")
-	    .build(LevelFilter::Info))?;
+    let log_path = init_logging(&options.log_file, options.log_level)?;
+    info!("Opponent: {}", options.opponent.get_name());
 
-    log4rs::init_config(config)?;
+    let mut game = match (&options.load, &options.mode) {
+        (_, cli::Mode::Replay(path)) => game::game::Game::replay(path)?,
+        (Some(path), _) => game::game::Game::load(path)?,
+        (None, _) => {
+            let seed = options.seed.unwrap_or_else(game::game::clock_seed);
+            let world = match &options.mission {
+                Some(path) => game::world::World::from_mission(&game::mission::Mission::read(path)?, seed)?,
+                None => game::world::World::new(seed),
+            };
+            game::game::Game::from_world(world)
+        }
+    };
+
+    if let cli::Mode::Headless(path) = &options.mode {
+        return run_headless(path, game);
+    }
+    if let cli::Mode::Play = options.mode {
+        game.set_replay_path(Some(game::replay::new_replay_path()));
+    }
 
-    let game = game::game::Game::new();
+    let mut keymap = keymap::Keymap::load_or_default(&options.keymap)?;
+    if let cli::Mode::Replay(_) = options.mode {
+        keymap.set_context(keymap::Context::Replay);
+    }
+    let mut theme = options.theme.load()?;
+    theme.double_width |= options.double_width;
 
-    enable_raw_mode().expect("can run in raw mode");
+    terminal::install_panic_hook();
+    let guard = terminal::Guard::enter()?;
 
     let stdout = io::stdout();
     let backend = CrosstermBackend::new(stdout);
@@ -105,12 +200,71 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     terminal.clear()?;
 
     let (tx, rx) = mpsc::channel();
-    event_loop(tx);
-    let game_exit_status = game_loop(&mut terminal, rx, game);
-
-    disable_raw_mode()?;
-    terminal.show_cursor()?;
-    println!();
+    let pump = events::InputPump::start(tx, Duration::from_millis(30));
+    let game_exit_status = game_loop(&mut terminal, rx, &pump, keymap, &theme, game, &log_path);
+    pump.stop();
 
+    drop(guard);
     game_exit_status
 }
+
+/// Show `path` in `$PAGER`, or `less`, the terminal handed over with input polling paused meanwhile
+fn view_file<B: Backend>(terminal: &mut Terminal<B>, pump: &events::InputPump, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let pager = env::var("PAGER").ok().filter(|p| !p.is_empty()).unwrap_or_else(|| "less".to_string());
+    pump.pause();
+    let status = terminal::hand_off(|| process::Command::new(&pager).arg(path).status());
+    pump.resume();
+    terminal.clear()?;
+    if let Err(e) = status? {
+        warn!("Cannot run {}: {}", pager, e);
+    }
+    Ok(())
+}
+
+/// Log to `path`, creating its directory, or to the temporary directory if that fails, returning the file used
+fn init_logging(path: &Path, level: LevelFilter) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let fallback = env::temp_dir().join("shulk.log");
+    let (logfile, used, fallback_reason) = match build_log_appender(path) {
+        Ok(logfile) => (logfile, path.to_path_buf(), None),
+        Err(e) => (build_log_appender(&fallback)?, fallback, Some(e)),
+    };
+
+    let config = Config::builder()
+	.appender(Appender::builder().build("logfile", Box::new(logfile)))
+	.build(Root::builder()
+	    .appender("logfile")
+	    .build(level))?;
+
+    log4rs::init_config(config)?;
+    if let Some(e) = fallback_reason {
+        warn!("Cannot log to {}: {}", path.display(), e);
+    }
+    Ok(used)
+}
+
+fn build_log_appender(path: &Path) -> Result<FileAppender, Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let logfile = FileAppender::builder()
+	.encoder(Box::new(PatternEncoder::new("{l} - {m}\n")))
+	.build(path)?;
+    Ok(logfile)
+}
+
+/// Play a script of inputs and print where everyone ended up
+fn run_headless(path: &Path, game: game::game::Game) -> Result<(), Box<dyn std::error::Error>> {
+    let script = fs::read_to_string(path)?;
+    let mut driver = headless::Driver::new(game);
+    let updates = driver.run(headless::parse_script(&script)?);
+
+    for entry in updates.iter().flat_map(|u| u.log.iter()) {
+        println!("{}", entry);
+    }
+    let world = driver.game().world();
+    println!("{} inputs played, turn {}", updates.len(), world.turn);
+    for t in world.terminators.iter() {
+        println!("{}: ({}, {}) facing {:?}, {} AP", t.name, t.pos.x, t.pos.y, t.pos.dir, t.aps);
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+
+use tui::{
+    backend::Backend,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, BorderType, Borders, Paragraph},
+    Frame,
+};
+
+use shulk::game::log_entry::LogEntry;
+use shulk::game::status;
+
+/// Columns of the panels right of the board
+pub const SIDE_WIDTH: u16 = 32;
+/// Rows of the unit panel, the turn panel taking the rest of the side
+pub const UNIT_HEIGHT: u16 = 8;
+
+fn panel(title: &str) -> Block<'_> {
+    Block::default()
+        .borders(Borders::ALL)
+        .style(Style::default().fg(Color::White))
+        .title(title)
+        .border_type(BorderType::Plain)
+}
+
+fn field<'a>(name: &'a str, value: String) -> Spans<'a> {
+    Spans::from(vec![
+        Span::styled(format!("{:<10}", name), Style::default().fg(Color::Gray)),
+        Span::raw(value),
+    ])
+}
+
+fn yes_no(b: bool) -> String {
+    if b { "yes".to_string() } else { "no".to_string() }
+}
+
+pub fn draw_unit<B: Backend>(frame: &mut Frame<B>, area: Rect, unit: Option<&status::UnitStatus>) {
+    let lines = match unit {
+        Some(u) => vec![
+            Spans::from(Span::styled(u.name.clone(), Style::default().add_modifier(Modifier::BOLD))),
+            field("Weapon", u.weapon.clone()),
+            field("AP", u.aps.to_string()),
+            field("Overwatch", yes_no(u.overwatch)),
+            field("Jammed", yes_no(u.jammed)),
+        ],
+        None => vec![Spans::from("No unit selected")],
+    };
+    frame.render_widget(Paragraph::new(lines).block(panel("Unit")), area);
+}
+
+pub fn draw_turn<B: Backend>(frame: &mut Frame<B>, area: Rect, turn: &status::TurnStatus) {
+    let lines = vec![
+        field("Turn", turn.turn.to_string()),
+        field("CP", turn.cps.to_string()),
+        field("Undo", if turn.can_undo { "available".to_string() } else { "locked".to_string() }),
+    ];
+    frame.render_widget(Paragraph::new(lines).block(panel("Turn")), area);
+}
+
+/// Messages of the whole game, newest last
+pub struct MessageLog {
+    lines: Vec<String>,
+    /// Lines hidden below the view, 0 following the newest
+    scroll: usize,
+}
+
+impl MessageLog {
+    pub fn new() -> MessageLog {
+        MessageLog{lines:vec![], scroll:0}
+    }
+
+    pub fn extend(&mut self, entries: &[LogEntry]) {
+        self.lines.extend(entries.iter().map(|e| e.to_string()));
+        if self.scroll > 0 {
+            self.scroll += entries.len();
+        }
+    }
+
+    pub fn scroll_up(&mut self, n: usize) {
+        self.scroll = (self.scroll + n).min(self.lines.len().saturating_sub(1));
+    }
+
+    pub fn scroll_down(&mut self, n: usize) {
+        self.scroll = self.scroll.saturating_sub(n);
+    }
+
+    pub fn draw<B: Backend>(&self, frame: &mut Frame<B>, area: Rect) {
+        let rows = area.height.saturating_sub(2) as usize;
+        let end = self.lines.len() - self.scroll.min(self.lines.len());
+        let start = end.saturating_sub(rows);
+        let lines: Vec<Spans> = self.lines[start..end].iter().map(|l| Spans::from(l.as_str())).collect();
+        let title = if self.scroll > 0 { format!("Log (-{})", self.scroll) } else { "Log".to_string() };
+        frame.render_widget(Paragraph::new(lines).block(panel(&title)), area);
+    }
+}
@@ -0,0 +1,179 @@
+
+use std::collections::VecDeque;
+
+use crate::graphics;
+
+/// Ticks a model takes to go from a square to the next
+const SLIDE_TICKS: u32 = 4;
+/// Ticks a muzzle flash stays on each square of the line of fire
+const FIRE_TICKS_PER_SQUARE: u32 = 1;
+/// Ticks flames take to spread to the next square
+const FLAME_TICKS_PER_SQUARE: u32 = 2;
+/// Ticks flames burn once all their squares are filled
+const FLAME_BURN_TICKS: u32 = 6;
+const FADE_TICKS: u32 = 12;
+
+/// Visual contents drawn by the animations
+const FLOOR: u32 = 1;
+const MUZZLE_FLASH: u32 = 17;
+const FLAME: u32 = 18;
+/// A fading model, from the most to the least visible
+const FADING: [u32; 2] = [19, 20];
+
+struct Animation {
+    effect: graphics::Effect,
+    elapsed: u32,
+}
+
+impl Animation {
+    fn duration(&self) -> u32 {
+        match &self.effect {
+            graphics::Effect::Slide{..} => SLIDE_TICKS,
+            graphics::Effect::Fire{squares} => squares.len().max(1) as u32 * FIRE_TICKS_PER_SQUARE,
+            graphics::Effect::Flame{squares} => squares.len() as u32 * FLAME_TICKS_PER_SQUARE + FLAME_BURN_TICKS,
+            graphics::Effect::Fade{..} => FADE_TICKS,
+        }
+    }
+
+    fn get_visuals(&self) -> Vec<graphics::Visual> {
+        let visual = |content: u32, (x, y): (u32, u32), color: graphics::Color| {
+            graphics::Visual{content, x, y, id:None, color, layer:graphics::Layer::Effects}
+        };
+        match &self.effect {
+            graphics::Effect::Slide{content, color, from, to} => {
+                let at = if self.elapsed < SLIDE_TICKS / 2 { *from } else { *to };
+                vec![visual(FLOOR, *to, graphics::Color::Default), visual(*content, at, *color)]
+            }
+            graphics::Effect::Fire{squares} => {
+                let square = squares.get((self.elapsed / FIRE_TICKS_PER_SQUARE) as usize);
+                square.map(|s| visual(MUZZLE_FLASH, *s, graphics::Color::Default)).into_iter().collect()
+            }
+            graphics::Effect::Flame{squares} => {
+                let lit = (self.elapsed / FLAME_TICKS_PER_SQUARE + 1) as usize;
+                squares.iter().take(lit).map(|s| visual(FLAME, *s, graphics::Color::Default)).collect()
+            }
+            graphics::Effect::Fade{content, color, x, y} => {
+                let content = match self.elapsed * 3 / FADE_TICKS {
+                    0 => *content,
+                    1 => FADING[0],
+                    _ => FADING[1],
+                };
+                vec![visual(content, (*x, *y), *color)]
+            }
+        }
+    }
+}
+
+/// Effects played one after the other, moved on by ticks
+pub struct Animations {
+    queue: VecDeque<Animation>,
+}
+
+impl Default for Animations {
+    fn default() -> Animations {
+        Animations::new()
+    }
+}
+
+impl Animations {
+    pub fn new() -> Animations {
+        Animations{queue:VecDeque::new()}
+    }
+
+    pub fn extend(&mut self, effects: &[graphics::Effect]) {
+        self.queue.extend(effects.iter().map(|e| Animation{effect:e.clone(), elapsed:0}));
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Move the current animation on, the next one starting once it is over
+    pub fn tick(&mut self) {
+        if let Some(current) = self.queue.front_mut() {
+            current.elapsed += 1;
+            if current.elapsed >= current.duration() {
+                self.queue.pop_front();
+            }
+        }
+    }
+
+    /// Fast forward to the end of all animations
+    pub fn skip(&mut self) {
+        self.queue.clear();
+    }
+
+    /// Visuals of the current frame. Models still to slide are hidden where they end.
+    pub fn get_visuals(&self) -> Vec<graphics::Visual> {
+        let mut visuals = Vec::<graphics::Visual>::new();
+        for a in self.queue.iter().skip(1) {
+            if let graphics::Effect::Slide{to:(x, y), ..} = a.effect {
+                visuals.push(graphics::Visual{content:FLOOR, x, y, id:None, color:graphics::Color::Default,
+                    layer:graphics::Layer::Effects});
+            }
+        }
+        if let Some(current) = self.queue.front() {
+            visuals.extend(current.get_visuals());
+        }
+        visuals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contents(animations: &Animations) -> Vec<(u32, u32, u32)> {
+        animations.get_visuals().iter().map(|v| (v.content, v.x, v.y)).collect()
+    }
+
+    #[test]
+    fn slides_play_one_after_the_other() {
+        let mut animations = Animations::new();
+        animations.extend(&[
+            graphics::Effect::Slide{content:4, color:graphics::Color::Default, from:(1, 1), to:(2, 1)},
+            graphics::Effect::Slide{content:4, color:graphics::Color::Default, from:(2, 1), to:(3, 1)},
+        ]);
+        // The model is hidden where it ends and drawn where it comes from
+        assert_eq!(contents(&animations), vec![(FLOOR, 3, 1), (FLOOR, 2, 1), (4, 1, 1)]);
+        for _ in 0..SLIDE_TICKS {
+            animations.tick();
+        }
+        assert_eq!(contents(&animations), vec![(FLOOR, 3, 1), (4, 2, 1)]);
+        for _ in 0..SLIDE_TICKS {
+            animations.tick();
+        }
+        assert!(!animations.is_playing());
+        assert!(animations.get_visuals().is_empty());
+    }
+
+    #[test]
+    fn flashes_travel_and_flames_spread() {
+        let squares = vec![(5, 2), (6, 2), (7, 2)];
+        let mut animations = Animations::new();
+        animations.extend(&[graphics::Effect::Fire{squares:squares.clone()}, graphics::Effect::Flame{squares}]);
+        assert_eq!(contents(&animations), vec![(MUZZLE_FLASH, 5, 2)]);
+        animations.tick();
+        assert_eq!(contents(&animations), vec![(MUZZLE_FLASH, 6, 2)]);
+        animations.tick();
+        animations.tick();
+        assert_eq!(contents(&animations), vec![(FLAME, 5, 2)]);
+        for _ in 0..FLAME_TICKS_PER_SQUARE {
+            animations.tick();
+        }
+        assert_eq!(contents(&animations), vec![(FLAME, 5, 2), (FLAME, 6, 2)]);
+    }
+
+    #[test]
+    fn skipping_ends_all_animations() {
+        let mut animations = Animations::new();
+        animations.extend(&[graphics::Effect::Fade{content:6, color:graphics::Color::Default, x:4, y:4}]);
+        assert_eq!(contents(&animations), vec![(6, 4, 4)]);
+        for _ in 0..FADE_TICKS / 2 {
+            animations.tick();
+        }
+        assert_eq!(contents(&animations), vec![(FADING[0], 4, 4)]);
+        animations.skip();
+        assert!(!animations.is_playing());
+    }
+}
@@ -1,34 +1,84 @@
 
 use std::iter;
-use std::iter::FromIterator;
+use tui::style::Style;
+use tui::text::{Span, Spans};
+
+/// A character and its colors
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cell {
+    pub c: char,
+    pub style: Style,
+}
+
+/// Line directions leaving a cell
+const UP: u8 = 1;
+const DOWN: u8 = 2;
+const LEFT: u8 = 4;
+const RIGHT: u8 = 8;
 
 pub struct Canvas {
     w: u32,
     h: u32,
-    chars: Vec<char>,
+    cells: Vec<Cell>,
+    /// Line directions of each cell, for lines meeting to be joined
+    lines: Vec<u8>,
 }
 
 impl Canvas {
     pub fn new(w: u32, h: u32) -> Canvas {
-        let chars: Vec<char> = iter::repeat(' ').take((w * h) as usize).collect();
-        Canvas{w, h, chars}
+        let blank = Cell{c:' ', style:Style::default()};
+        let cells: Vec<Cell> = iter::repeat_n(blank, (w * h) as usize).collect();
+        Canvas{w, h, cells, lines:vec![0; (w * h) as usize]}
+    }
+
+    pub fn draw_styled_char(&mut self, x: u32, y: u32, c: char, style: Style) {
+        if x >= self.w || y >= self.h {
+            return;
+        }
+        let offset = (y * self.w + x) as usize;
+        self.cells[offset] = Cell{c, style};
+        self.lines[offset] = 0;
+    }
+
+    /// Draw a character keeping the background of the cell, unless `style` has one
+    pub fn draw_over(&mut self, x: u32, y: u32, c: char, style: Style) {
+        if x >= self.w || y >= self.h {
+            return;
+        }
+        let under = Style{bg:self.cells[(y * self.w + x) as usize].style.bg, ..Style::default()};
+        self.draw_styled_char(x, y, c, under.patch(style));
+    }
+
+    /// Add colors or modifiers to a cell, keeping its character
+    pub fn patch_style(&mut self, x: u32, y: u32, style: Style) {
+        if x >= self.w || y >= self.h {
+            return;
+        }
+        let cell = &mut self.cells[(y * self.w + x) as usize];
+        cell.style = cell.style.patch(style);
     }
 
-    pub fn draw_char(&mut self, x: u32, y: u32, c: char) {
+    /// Add line directions to a cell, drawn joined with those already there
+    fn add_line(&mut self, x: u32, y: u32, directions: u8) {
         if x >= self.w || y >= self.h {
             return;
         }
         let offset = (y * self.w + x) as usize;
-        self.chars[offset] = c;
+        self.lines[offset] |= directions;
+        self.cells[offset] = Cell{c:get_line_glyph(self.lines[offset]), style:Style::default()};
     }
 
     pub fn draw_string(&mut self, x: u32, y: u32, s: &str) {
+        self.draw_styled_string(x, y, s, Style::default());
+    }
+
+    pub fn draw_styled_string(&mut self, x: u32, y: u32, s: &str, style: Style) {
         if x >= self.w || y >= self.h {
             return;
         }
         let mut i = x;
         for c in s.chars() {
-            self.draw_char(i, y, c);
+            self.draw_styled_char(i, y, c, style);
 
             i += 1;
             if i >= self.w {
@@ -38,51 +88,83 @@ impl Canvas {
     }
 
     pub fn draw_box(&mut self, x: u32, y: u32, w: u32, h: u32) {
-        self.draw_char(x, y, '┌');
-        self.draw_char(x+w, y, '┐');
-        self.draw_char(x, y+h-1, '└');
-        self.draw_char(x+w, y+h-1, '┘');
+        self.add_line(x, y, RIGHT | DOWN);
+        self.add_line(x+w, y, LEFT | DOWN);
+        self.add_line(x, y+h-1, RIGHT | UP);
+        self.add_line(x+w, y+h-1, LEFT | UP);
 
         for i in x+1..x+w {
-            self.draw_char(i, y, '─');
-            self.draw_char(i, y+h-1, '─');
+            self.add_line(i, y, LEFT | RIGHT);
+            self.add_line(i, y+h-1, LEFT | RIGHT);
         }
         for i in y+1..(y+h-1) {
-            self.draw_char(x, i, '│');
-            self.draw_char(x+w, i, '│');
+            self.add_line(x, i, UP | DOWN);
+            self.add_line(x+w, i, UP | DOWN);
         }
     }
 
-    pub fn draw_connector(&mut self, x0: u32, y0: u32, x1: u32, y1: u32) {
-        for x in x0+1..x1 {
-            self.draw_char(x, y0, '─');
-        }
-        if y0 == y1 {
-            return;
-        }
-        if y0 > y1 {
-            self.draw_char(x1, y0, '┘');
-            for y in y1+1..y0 {
-                self.draw_char(x1, y, '│');
+    /// Line through adjacent `cells`, the last one left untouched
+    pub fn draw_line(&mut self, cells: &[(u32, u32)]) {
+        for (i, pair) in cells.windows(2).enumerate() {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            let (out, back) = if x1 > x0 {
+                (RIGHT, LEFT)
             }
-        }
-        else {
-            self.draw_char(x1, y0, '┐');
-            for y in y0+1..y1 {
-                self.draw_char(x1, y, '│');
+            else if x1 < x0 {
+                (LEFT, RIGHT)
+            }
+            else if y1 > y0 {
+                (DOWN, UP)
             }
+            else {
+                (UP, DOWN)
+            };
+            self.add_line(x0, y0, out);
+            if i + 2 < cells.len() {
+                self.add_line(x1, y1, back);
+            }
+        }
+    }
+
+    /// Replace box-drawing characters by `+`, `-` and `|`
+    pub fn use_ascii_lines(&mut self) {
+        for cell in self.cells.iter_mut() {
+            cell.c = match cell.c {
+                '─' => '-',
+                '│' => '|',
+                '┌' | '┐' | '└' | '┘' | '├' | '┤' | '┬' | '┴' | '┼' => '+',
+                c => c,
+            };
         }
     }
 
-    pub fn to_string(&self) -> String {
-        let len = self.chars.len() + self.h as usize;
-        let mut chars = Vec::<char>::with_capacity(len);
+    /// One line per row, consecutive cells of the same style sharing a span
+    pub fn to_spans(&self) -> Vec<Spans<'static>> {
+        let mut lines = Vec::<Spans>::with_capacity(self.h as usize);
         for y in 0..self.h {
             let from = (y * self.w) as usize;
             let to = from + self.w as usize;
-            chars.extend_from_slice(&self.chars[from..to]);
-            chars.push('\n');
+            let mut spans = Vec::<Span>::new();
+            let mut text = String::new();
+            let mut style = Style::default();
+            for cell in self.cells[from..to].iter() {
+                if cell.style != style && !text.is_empty() {
+                    spans.push(Span::styled(text, style));
+                    text = String::new();
+                }
+                style = cell.style;
+                text.push(cell.c);
+            }
+            spans.push(Span::styled(text, style));
+            lines.push(Spans::from(spans));
         }
-        String::from_iter(chars)
+        lines
     }
 }
+
+/// Box-drawing characters indexed by line directions
+const LINE_GLYPHS: [char; 16] = [' ', '│', '│', '│', '─', '┘', '┐', '┤', '─', '└', '┌', '├', '─', '┴', '┬', '┼'];
+
+fn get_line_glyph(directions: u8) -> char {
+    LINE_GLYPHS[(directions & 15) as usize]
+}
@@ -0,0 +1,206 @@
+
+use std::cmp;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Point {
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Point {
+    pub fn manhattan(&self, other: &Point) -> u32 {
+        let dx = cmp::max(self.x, other.x) - cmp::min(self.x, other.x);
+        let dy = cmp::max(self.y, other.y) - cmp::min(self.y, other.y);
+        dx + dy
+    }
+}
+
+/// Horizontal or vertical segment, `p1` being the top or left end
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Segment {
+    pub p1: Point,
+    pub p2: Point,
+}
+
+impl Segment {
+    /// Segment between two points on a same row or column, in any order
+    pub fn new(a: Point, b: Point) -> Segment {
+        debug_assert!(a.x == b.x || a.y == b.y, "segment must be axis-aligned");
+        if (a.x, a.y) <= (b.x, b.y) {
+            Segment{p1:a, p2:b}
+        }
+        else {
+            Segment{p1:b, p2:a}
+        }
+    }
+
+    /// A single point is both horizontal and vertical
+    pub fn is_horizontal(&self) -> bool {
+        self.p1.y == self.p2.y
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        self.p1.x == self.p2.x
+    }
+
+    pub fn length(&self) -> u32 {
+        self.p1.manhattan(&self.p2)
+    }
+
+    pub fn contains(&self, p: &Point) -> bool {
+        p.x >= self.p1.x && p.x <= self.p2.x && p.y >= self.p1.y && p.y <= self.p2.y
+    }
+
+    /// Points of the segment from `p1` to `p2`
+    pub fn points(&self) -> Vec<Point> {
+        let mut points = vec![];
+        for x in self.p1.x..=self.p2.x {
+            for y in self.p1.y..=self.p2.y {
+                points.push(Point{x, y});
+            }
+        }
+        points
+    }
+
+    /// Length shared by two segments on a same line, 0 if they only touch or are not aligned
+    pub fn get_common(a: &Segment, b: &Segment) -> u32 {
+        if a.is_vertical() && b.is_vertical() && a.p1.x == b.p1.x {
+            let start = cmp::max(a.p1.y, b.p1.y);
+            let end = cmp::min(a.p2.y, b.p2.y);
+            return end.saturating_sub(start);
+        }
+        if a.is_horizontal() && b.is_horizontal() && a.p1.y == b.p1.y {
+            let start = cmp::max(a.p1.x, b.p1.x);
+            let end = cmp::min(a.p2.x, b.p2.x);
+            return end.saturating_sub(start);
+        }
+        0
+    }
+
+    /// Whether a horizontal and a vertical segment cross or touch
+    pub fn are_crossing(a: &Segment, b: &Segment) -> bool {
+        let (h, v) = if a.is_horizontal() && b.is_vertical() {
+            (a, b)
+        }
+        else if b.is_horizontal() && a.is_vertical() {
+            (b, a)
+        }
+        else {
+            return false;
+        };
+        h.contains(&Point{x:v.p1.x, y:h.p1.y}) && v.contains(&Point{x:v.p1.x, y:h.p1.y})
+    }
+}
+
+/// Area of `w` x `h` squares from its top left corner
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Rect {
+    pub fn contains(&self, p: &Point) -> bool {
+        p.x >= self.x && p.x < self.x + self.w && p.y >= self.y && p.y < self.y + self.h
+    }
+
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        other.x >= self.x && other.y >= self.y
+            && other.x + other.w <= self.x + self.w && other.y + other.h <= self.y + self.h
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.w && other.x < self.x + self.w
+            && self.y < other.y + other.h && other.y < self.y + self.h
+    }
+
+    pub fn intersects_segment(&self, s: &Segment) -> bool {
+        if self.w == 0 || self.h == 0 {
+            return false;
+        }
+        s.p1.x < self.x + self.w && s.p2.x >= self.x && s.p1.y < self.y + self.h && s.p2.y >= self.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: u32, y: u32) -> Point {
+        Point{x, y}
+    }
+
+    #[test]
+    fn manhattan_distance() {
+        assert_eq!(p(1, 2).manhattan(&p(4, 0)), 5);
+        assert_eq!(p(4, 0).manhattan(&p(1, 2)), 5);
+        assert_eq!(p(3, 3).manhattan(&p(3, 3)), 0);
+    }
+
+    #[test]
+    fn new_segment_orders_ends() {
+        let s = Segment::new(p(5, 2), p(1, 2));
+        assert_eq!(s.p1, p(1, 2));
+        assert_eq!(s.p2, p(5, 2));
+        assert_eq!(s.length(), 4);
+        assert!(s.is_horizontal() && !s.is_vertical());
+        assert_eq!(s.points(), vec![p(1, 2), p(2, 2), p(3, 2), p(4, 2), p(5, 2)]);
+    }
+
+    #[test]
+    fn segment_contains() {
+        let s = Segment::new(p(2, 1), p(2, 4));
+        assert!(s.contains(&p(2, 1)));
+        assert!(s.contains(&p(2, 4)));
+        assert!(!s.contains(&p(2, 5)));
+        assert!(!s.contains(&p(3, 2)));
+    }
+
+    #[test]
+    fn common_length() {
+        let a = Segment::new(p(3, 0), p(3, 5));
+        assert_eq!(Segment::get_common(&a, &Segment::new(p(3, 2), p(3, 9))), 3);
+        assert_eq!(Segment::get_common(&a, &Segment::new(p(3, 1), p(3, 2))), 1);
+        assert_eq!(Segment::get_common(&a, &Segment::new(p(3, 5), p(3, 8))), 0);
+        assert_eq!(Segment::get_common(&a, &Segment::new(p(4, 0), p(4, 5))), 0);
+        assert_eq!(Segment::get_common(&a, &Segment::new(p(0, 2), p(6, 2))), 0);
+
+        let h = Segment::new(p(0, 7), p(6, 7));
+        assert_eq!(Segment::get_common(&h, &Segment::new(p(4, 7), p(10, 7))), 2);
+    }
+
+    #[test]
+    fn crossing() {
+        let h = Segment::new(p(0, 3), p(6, 3));
+        assert!(Segment::are_crossing(&h, &Segment::new(p(2, 0), p(2, 5))));
+        assert!(Segment::are_crossing(&Segment::new(p(2, 0), p(2, 5)), &h));
+        assert!(Segment::are_crossing(&h, &Segment::new(p(6, 3), p(6, 5))));
+        assert!(!Segment::are_crossing(&h, &Segment::new(p(7, 0), p(7, 5))));
+        assert!(!Segment::are_crossing(&h, &Segment::new(p(2, 4), p(2, 5))));
+        assert!(!Segment::are_crossing(&h, &Segment::new(p(0, 4), p(6, 4))));
+    }
+
+    #[test]
+    fn rect_containment() {
+        let r = Rect{x:2, y:2, w:4, h:3};
+        assert!(r.contains(&p(2, 2)));
+        assert!(r.contains(&p(5, 4)));
+        assert!(!r.contains(&p(6, 4)));
+        assert!(!r.contains(&p(5, 5)));
+        assert!(r.contains_rect(&Rect{x:3, y:2, w:3, h:2}));
+        assert!(!r.contains_rect(&Rect{x:3, y:2, w:4, h:2}));
+    }
+
+    #[test]
+    fn rect_intersection() {
+        let r = Rect{x:2, y:2, w:4, h:3};
+        assert!(r.intersects(&Rect{x:5, y:4, w:2, h:2}));
+        assert!(!r.intersects(&Rect{x:6, y:2, w:2, h:2}));
+        assert!(r.intersects_segment(&Segment::new(p(0, 3), p(2, 3))));
+        assert!(!r.intersects_segment(&Segment::new(p(0, 3), p(1, 3))));
+        assert!(r.intersects_segment(&Segment::new(p(4, 0), p(4, 9))));
+        assert!(!r.intersects_segment(&Segment::new(p(4, 5), p(4, 9))));
+    }
+}
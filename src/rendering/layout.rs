@@ -1,6 +1,6 @@
 
 use std::cmp;
-use log::info;
+use std::collections::HashMap;
 
 use crate::graphics;
 use super::geometry;
@@ -15,121 +15,315 @@ pub fn get_span(visuals: &[graphics::Visual]) -> (u32, u32) {
     (w, h)
 }
 
-pub fn get_max_text_len(texts: &[graphics::Text]) -> u32 {
-    let mut m: u32 = 0;
-    for t in texts.iter() {
-        match t {
-            graphics::Text::ItemAnnotation(_, s) => m = cmp::max(m, s.len() as u32),
-            _ => (),
-        }
+/// Gutter of the board where an annotation box is placed
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+/// Columns left and right of the board, rows above and under it
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct Gutters {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Gutters {
+    pub fn width(&self) -> u32 {
+        self.left + self.right
+    }
+
+    pub fn height(&self) -> u32 {
+        self.top + self.bottom
+    }
+
+    /// Largest of both on each side
+    pub fn max(&self, other: &Gutters) -> Gutters {
+        Gutters{left:cmp::max(self.left, other.left), right:cmp::max(self.right, other.right),
+            top:cmp::max(self.top, other.top), bottom:cmp::max(self.bottom, other.bottom)}
+    }
+
+    pub fn contains(&self, other: &Gutters) -> bool {
+        self.max(other) == *self
+    }
+}
+
+/// Columns between a side box and the board
+const SIDE_GAP: u32 = 2;
+/// Columns past the middle of the board a target can go before its box changes side
+const SIDE_HYSTERESIS: u32 = 3;
+/// Cost a kept column layout may gain over its optimized cost before being optimized again
+const REOPTIMIZE_MARGIN: u32 = 8;
+/// Rows between the board and a top or bottom box, for its connector to run along
+const ROW_GAP: u32 = 1;
+
+/// Which text of a unit a box shows, a unit having at most one of each
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Kind {
+    Annotation,
+    Details,
+}
+
+/// Placements kept from a frame to the next, for boxes to stay put while units move
+#[derive(Clone, Default)]
+pub struct LayoutState {
+    /// Side and box row from the board top of each annotation, by id and kind
+    placements: HashMap<(u32, Kind), (Side, u32)>,
+    /// Cost of the left and right columns when last optimized
+    costs: HashMap<Side, u32>,
+}
+
+impl LayoutState {
+    pub fn new() -> LayoutState {
+        LayoutState{placements:HashMap::new(), costs:HashMap::new()}
     }
-    m
 }
 
 pub struct Annotation {
     pub id: u32,
-    pub text: String,
+    pub kind: Kind,
+    pub lines: Vec<String>,
     pub w: u32,
     pub h: u32,
     pub target_pos: geometry::Point,
     pub pos: geometry::Point,
+    pub side: Side,
 }
 
-pub fn collect(visuals: &[graphics::Visual], texts: &[graphics::Text]) -> Vec<Annotation> {
-    let visual_ids: Vec<&graphics::Visual> = visuals.iter().filter(|v| match v.id {
-        Some(id) => true,
-        _ => false,
-    }).collect();
+impl Annotation {
+    /// Box cells, borders included
+    pub fn get_rect(&self) -> geometry::Rect {
+        geometry::Rect{x:self.pos.x, y:self.pos.y, w:self.w + 1, h:self.h}
+    }
+
+    /// Border cell the connector leaves from, and the cell next to it outside the box
+    pub fn get_exit(&self) -> (geometry::Point, geometry::Point) {
+        let p = |x: u32, y: u32| geometry::Point{x, y};
+        let y = self.pos.y + 1;
+        let x = cmp::min(cmp::max(self.target_pos.x, self.pos.x + 1), self.pos.x + self.w - 1);
+        match self.side {
+            Side::Left => (p(self.pos.x + self.w, y), p(self.pos.x + self.w + 1, y)),
+            Side::Right => (p(self.pos.x, y), p(self.pos.x.saturating_sub(1), y)),
+            Side::Top => (p(x, self.pos.y + self.h - 1), p(x, self.pos.y + self.h)),
+            Side::Bottom => (p(x, self.pos.y), p(x, self.pos.y.saturating_sub(1))),
+        }
+    }
+
+    /// Points where the plain connector from the box border to its target starts and turns, ending on the target
+    pub fn get_path(&self) -> Vec<geometry::Point> {
+        let t = self.target_pos;
+        let (border, exit) = self.get_exit();
+        match self.side {
+            Side::Left | Side::Right => {
+                if border.y == t.y { vec![border, t] } else { vec![border, geometry::Point{x:t.x, y:border.y}, t] }
+            }
+            Side::Top | Side::Bottom => {
+                if border.x == t.x { vec![border, t] } else { vec![border, exit, geometry::Point{x:t.x, y:exit.y}, t] }
+            }
+        }
+    }
+}
+
+impl Annotation {
+    /// Box of `lines` framed by its borders, targeting `target_pos`
+    pub fn new(id: u32, kind: Kind, lines: Vec<String>, target_pos: geometry::Point) -> Annotation {
+        let w = lines.iter().map(|l| l.chars().count() as u32).max().unwrap_or(0) + 2;
+        let h = lines.len() as u32 + 2;
+        Annotation{id, kind, lines, w, h, target_pos, pos:geometry::Point{x:0, y:0}, side:Side::Left}
+    }
+
+    /// What its placement is kept under
+    fn key(&self) -> (u32, Kind) {
+        (self.id, self.kind)
+    }
+}
 
+pub fn collect(visuals: &[graphics::Visual], texts: &[graphics::Text]) -> Vec<Annotation> {
     let mut annotations = Vec::<Annotation>::new();
-    for v in visual_ids.iter() {
-        match v.id {
-            Some(v_id) => {
-                info!("test");
-                for t in texts.iter() {
-                    match t {
-                        graphics::Text::ItemAnnotation(a_id, s) => {
-                            if v_id == *a_id {
-                                let v_pos = geometry::Point{x:v.x, y:v.y};
-                                let annotation = Annotation{id: v_id, text:s.clone(), w:s.len() as u32 + 2, h:3, target_pos:v_pos, pos: geometry::Point{x:0, y:0}};
-                                annotations.push(annotation);
-                            }
-                        },
-                        _ => (),
-                    }
-                }
+    for v in visuals.iter() {
+        if let Some(v_id) = v.id {
+            for t in texts.iter() {
+                let (kind, lines) = match t {
+                    graphics::Text::ItemAnnotation(a_id, s) if v_id == *a_id => (Kind::Annotation, vec![s.clone()]),
+                    graphics::Text::ItemDetails(a_id, lines) if v_id == *a_id && !lines.is_empty() => (Kind::Details, lines.clone()),
+                    _ => continue,
+                };
+                annotations.push(Annotation::new(v_id, kind, lines, geometry::Point{x:v.x, y:v.y}));
             }
-            _ => (),
         }
     }
     annotations
 }
 
-pub fn place(annotations: &mut [Annotation]) {
-    if annotations.len() == 0 {
-        return
+/// Place the annotations of a `board_w` x `board_h` board drawn at `origin`, returning the gutters needed.
+/// Targets go from board to canvas coordinates. Connectors avoid running over `obstacles`.
+/// Boxes are kept where `state` last put them until that gets too costly.
+pub fn place(annotations: &mut [Annotation], obstacles: &[geometry::Point], state: &mut LayoutState,
+             origin: geometry::Point, board_w: u32, board_h: u32) -> Gutters {
+    let mut gutters = Gutters::default();
+    if annotations.is_empty() {
+        return gutters;
     }
 
-    annotations.sort_by_key(|a| a.target_pos.y * 1000 + a.target_pos.x);
-
-    let mut i = 0;
+    // Sides by target position, side columns overflowing above and under the board
+    annotations.sort_by_key(|a| (a.target_pos.y, a.target_pos.x, a.key()));
+    let on_left: Vec<bool> = annotations.iter().map(|a| {
+        let x = a.target_pos.x * 2;
+        match state.placements.get(&a.key()) {
+            Some((Side::Left, _)) if x < board_w + 2 * SIDE_HYSTERESIS => true,
+            Some((Side::Right, _)) if x + 2 * SIDE_HYSTERESIS >= board_w => false,
+            _ => x < board_w,
+        }
+    }).collect();
+    for side in [Side::Left, Side::Right].iter() {
+        let mut column: Vec<usize> = (0..annotations.len())
+            .filter(|i| on_left[*i] == (*side == Side::Left))
+            .collect();
+        while column.len() > 1 && column.iter().map(|i| annotations[*i].h).sum::<u32>() > cmp::max(board_h, 3) {
+            let first = annotations[column[0]].target_pos.y;
+            let last = annotations[column[column.len() - 1]].target_pos.y;
+            if first < board_h.saturating_sub(1 + last) {
+                annotations[column.remove(0)].side = Side::Top;
+            }
+            else {
+                annotations[column.pop().unwrap()].side = Side::Bottom;
+            }
+        }
+        for i in column {
+            annotations[i].side = *side;
+        }
+    }
     for a in annotations.iter_mut() {
-        a.pos = geometry::Point{x:0, y:i};
-        i += 3
+        a.target_pos = geometry::Point{x:origin.x + a.target_pos.x, y:origin.y + a.target_pos.y};
     }
+    let obstacles: Vec<geometry::Point> = obstacles.iter().map(|o| geometry::Point{x:origin.x + o.x, y:origin.y + o.y}).collect();
 
-    // build layout
-    let mut layout: Vec<LayoutAnnotation> = vec![];
-    for a in annotations.iter() {
-        layout.push(LayoutAnnotation{id:a.id, y:a.pos.y, target:a.target_pos});
+    for side in [Side::Left, Side::Right].iter() {
+        let column: Vec<&mut Annotation> = annotations.iter_mut().filter(|a| a.side == *side).collect();
+        let width = column.iter().map(|a| a.w + 1 + SIDE_GAP).max().unwrap_or(0);
+        match side {
+            Side::Left => gutters.left = width,
+            _ => gutters.right = width,
+        }
+        place_column(column, &obstacles, state, origin, board_w, board_h, width, *side);
     }
-
-    // optimize
-    loop {
-        let mut moves = build_all_moves(&layout);
-        moves.sort_by_key(|a| evaluate_move(a));
-        let best = moves[0].clone();
-        if evaluate_move(&best) >= evaluate_move(&layout) {
-            break;
+    for side in [Side::Top, Side::Bottom].iter() {
+        let row: Vec<&mut Annotation> = annotations.iter_mut().filter(|a| a.side == *side).collect();
+        let height = match row.iter().map(|a| a.h + ROW_GAP).max() {
+            Some(height) => height,
+            None => continue,
+        };
+        match side {
+            Side::Top => gutters.top = height,
+            _ => gutters.bottom = height,
         }
-        layout = best;
+        place_row(row, origin, board_h, *side);
+    }
+    for a in annotations.iter().filter(|a| a.side == Side::Top || a.side == Side::Bottom) {
+        state.placements.insert(a.key(), (a.side, 0));
+    }
+    gutters
+}
+
+/// Stack boxes beside the board, ordered and shifted to limit crossing connectors
+#[allow(clippy::too_many_arguments)]
+fn place_column(column: Vec<&mut Annotation>, obstacles: &[geometry::Point], state: &mut LayoutState, origin: geometry::Point,
+                board_w: u32, board_h: u32, width: u32, side: Side) {
+    if column.is_empty() {
+        return;
     }
+    let anchor_x = match side {
+        Side::Left => origin.x.saturating_sub(SIDE_GAP),
+        _ => origin.x + board_w + SIDE_GAP - 1,
+    };
+    let box_x = match side {
+        Side::Left => origin.x.saturating_sub(width),
+        _ => origin.x + board_w + SIDE_GAP,
+    };
 
-    // apply layout
-    for l in layout.iter() {
-        for a in annotations.iter_mut() {
-            if a.id == l.id {
-                a.pos.y = l.y;
+    let column_h = cmp::max(board_h, column.iter().map(|a| a.h).sum());
+    let search = Search{
+        targets: column.iter().map(|a| a.target_pos).collect(),
+        heights: column.iter().map(|a| a.h).collect(),
+        obstacles,
+        anchor_x,
+        top: origin.y,
+        end: origin.y + column_h,
+    };
+
+    // Previous rows while still valid and not much worse than when optimized
+    let kept: Option<Vec<u32>> = column.iter()
+        .map(|a| match state.placements.get(&a.key()) {
+            Some((s, dy)) if *s == side => Some(origin.y + dy),
+            _ => None,
+        })
+        .collect();
+    let limit = state.costs.get(&side).map(|c| c + REOPTIMIZE_MARGIN);
+    let ys = match kept.filter(|ys| search.is_valid(ys) && Some(search.evaluate(ys)) <= limit) {
+        Some(ys) => ys,
+        None => {
+            let ys = if column.len() <= EXHAUSTIVE_MAX {
+                search.exhaustive()
             }
+            else {
+                search.anneal()
+            };
+            state.costs.insert(side, search.evaluate(&ys));
+            ys
         }
+    };
+    for (a, y) in column.into_iter().zip(ys) {
+        a.pos = geometry::Point{x:box_x, y};
+        state.placements.insert(a.key(), (side, y - origin.y));
+    }
+}
+
+/// Line boxes up above or under the board, each as close as possible over its target
+fn place_row(mut row: Vec<&mut Annotation>, origin: geometry::Point, board_h: u32, side: Side) {
+    row.sort_by_key(|a| a.target_pos.x);
+    let mut next_x = 0;
+    for a in row.iter_mut() {
+        let x = cmp::max(next_x, a.target_pos.x.saturating_sub(a.w / 2));
+        let y = match side {
+            Side::Top => origin.y.saturating_sub(ROW_GAP + a.h),
+            _ => origin.y + board_h + ROW_GAP,
+        };
+        a.pos = geometry::Point{x, y};
+        next_x = x + a.w + 2;
     }
 }
 
 #[derive(Clone)]
 pub struct LayoutAnnotation {
-    pub id: u32,
     pub y: u32,
     pub target: geometry::Point,
+    /// Column where the connector leaves the box side
+    pub anchor_x: u32,
 }
 
 impl LayoutAnnotation {
     fn get_anchor(&self) -> geometry::Point {
-        geometry::Point{x:0, y:self.y + 1}
+        geometry::Point{x:self.anchor_x, y:self.y + 1}
     }
 
     fn get_horizontal(&self) -> geometry::Segment {
         let anchor = self.get_anchor();
         let target = geometry::Point{x:self.target.x, y:anchor.y};
-        geometry::Segment{p1:anchor, p2:target}
+        geometry::Segment::new(anchor, target)
     }
 
     fn get_vertical(&self) -> geometry::Segment {
         let anchor = geometry::Point{x:self.target.x, y:self.get_anchor().y};
         if self.target.y > anchor.y {
-            return geometry::Segment{p1:anchor, p2:self.target.clone()};
+            geometry::Segment{p1:anchor, p2:self.target}
         }
         else {
-            return geometry::Segment{p1:self.target.clone(), p2:anchor};
+            geometry::Segment{p1:self.target, p2:anchor}
         }
     }
 
@@ -141,41 +335,300 @@ impl LayoutAnnotation {
         if geometry::Segment::are_crossing(&a1.get_horizontal(), &a2.get_vertical()) || geometry::Segment::are_crossing(&a2.get_horizontal(), &a1.get_vertical()) {
             return 100;
         }
-        return 0;
+        0
+    }
+
+    /// Obstacles on the connector, its target excepted
+    fn count_crossed(&self, obstacles: &[geometry::Point]) -> u32 {
+        let (h, v) = (self.get_horizontal(), self.get_vertical());
+        obstacles.iter().filter(|o| **o != self.target && (h.contains(o) || v.contains(o))).count() as u32
     }
 }
 
-fn build_all_moves(init: &[LayoutAnnotation]) -> Vec<Vec<LayoutAnnotation>> {
-    let mut res: Vec<Vec<LayoutAnnotation>> = vec![];
-    for i in 0..init.len()-1  {
-        res.push(swap_two(init, i));
+/// Orderings of a column tried exhaustively up to this many boxes, annealed beyond
+const EXHAUSTIVE_MAX: usize = 6;
+const ANNEALING_STEPS: u32 = 3000;
+const ANNEALING_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+/// Rows a box may be moved away from its target row while annealing
+const MAX_SHIFT: i32 = 3;
+/// Cost of a connector running over a unit or another glyph
+const GLYPH_PENALTY: u32 = 20;
+
+/// Box rows of a column, searched by order and shift from their target rows
+struct Search<'a> {
+    targets: Vec<geometry::Point>,
+    heights: Vec<u32>,
+    obstacles: &'a [geometry::Point],
+    anchor_x: u32,
+    /// Rows of the column, from its first to the one after its last
+    top: u32,
+    end: u32,
+}
+
+impl<'a> Search<'a> {
+    /// Box rows, in target order, stacking boxes from top to bottom in `order`
+    /// as close as possible to their target rows moved by `shifts`
+    fn stack(&self, order: &[usize], shifts: &[i32]) -> Vec<u32> {
+        let mut stacked = Vec::<u32>::with_capacity(order.len());
+        for (k, i) in order.iter().enumerate() {
+            let desired = self.targets[*i].y as i32 - 1 + shifts[*i];
+            let bottom = self.end.saturating_sub(self.heights[*i]);
+            let desired = cmp::min(cmp::max(desired, self.top as i32), bottom as i32) as u32;
+            let y = match k {
+                0 => desired,
+                _ => cmp::max(desired, stacked[k - 1] + self.heights[order[k - 1]]),
+            };
+            stacked.push(y);
+        }
+        let mut limit = self.end;
+        for (k, y) in stacked.iter_mut().enumerate().rev() {
+            *y = cmp::min(*y, limit.saturating_sub(self.heights[order[k]]));
+            limit = *y;
+        }
+
+        let mut ys = vec![0; order.len()];
+        for (k, i) in order.iter().enumerate() {
+            ys[*i] = stacked[k];
+        }
+        ys
+    }
+
+    /// Rows within the column, boxes apart
+    fn is_valid(&self, ys: &[u32]) -> bool {
+        let mut boxes: Vec<(u32, u32)> = ys.iter().cloned().zip(self.heights.iter().cloned()).collect();
+        boxes.sort_unstable();
+        boxes.iter().all(|(y, h)| *y >= self.top && y + h <= self.end) && boxes.windows(2).all(|w| w[1].0 >= w[0].0 + w[0].1)
+    }
+
+    fn evaluate(&self, ys: &[u32]) -> u32 {
+        let layout: Vec<LayoutAnnotation> = ys.iter().zip(self.targets.iter())
+            .map(|(y, t)| LayoutAnnotation{y:*y, target:*t, anchor_x:self.anchor_x})
+            .collect();
+        let mut score: u32 = 0;
+        for (i, a) in layout.iter().enumerate() {
+            for b in layout[i+1..].iter() {
+                score += LayoutAnnotation::get_overlap_score(a, b);
+            }
+            score += GLYPH_PENALTY * a.count_crossed(self.obstacles);
+            score += a.get_anchor().manhattan(&geometry::Point{x:self.anchor_x, y:a.target.y});
+        }
+        score
+    }
+
+    /// Best of all orders with boxes next to their targets, then each box shifted in turn
+    fn exhaustive(&self) -> Vec<u32> {
+        let n = self.targets.len();
+        let mut shifts = vec![0; n];
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by_key(|i| self.targets[*i].y);
+        let mut best_order = order.clone();
+        let mut best_score = self.evaluate(&self.stack(&order, &shifts));
+
+        // Heap's algorithm
+        let mut counters = vec![0; n];
+        let mut k = 1;
+        while k < n {
+            if counters[k] < k {
+                let swapped = if k % 2 == 0 { 0 } else { counters[k] };
+                order.swap(swapped, k);
+                let score = self.evaluate(&self.stack(&order, &shifts));
+                if score < best_score {
+                    best_order = order.clone();
+                    best_score = score;
+                }
+                counters[k] += 1;
+                k = 1;
+            }
+            else {
+                counters[k] = 0;
+                k += 1;
+            }
+        }
+
+        for i in 0..n {
+            let mut best_shift = 0;
+            for shift in -MAX_SHIFT..=MAX_SHIFT {
+                shifts[i] = shift;
+                let score = self.evaluate(&self.stack(&best_order, &shifts));
+                if score < best_score {
+                    best_shift = shift;
+                    best_score = score;
+                }
+            }
+            shifts[i] = best_shift;
+        }
+        self.stack(&best_order, &shifts)
+    }
+
+    /// Simulated annealing over orders and shifts, from boxes in target order
+    fn anneal(&self) -> Vec<u32> {
+        let n = self.targets.len();
+        let mut rng = Rng{state:ANNEALING_SEED};
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by_key(|i| self.targets[*i].y);
+        let mut shifts = vec![0; n];
+        let mut score = self.evaluate(&self.stack(&order, &shifts));
+        let mut best = (self.stack(&order, &shifts), score);
+
+        for step in 0..ANNEALING_STEPS {
+            let temperature = 50.0 * (1.0 - step as f64 / ANNEALING_STEPS as f64) + 0.1;
+            let (mut new_order, mut new_shifts) = (order.clone(), shifts.clone());
+            if rng.below(2) == 0 {
+                new_order.swap(rng.below(n), rng.below(n));
+            }
+            else {
+                let i = rng.below(n);
+                let delta = if rng.below(2) == 0 { -1 } else { 1 };
+                new_shifts[i] = (new_shifts[i] + delta).clamp(-MAX_SHIFT, MAX_SHIFT);
+            }
+            let ys = self.stack(&new_order, &new_shifts);
+            let new_score = self.evaluate(&ys);
+            let worse = new_score as f64 - score as f64;
+            if worse <= 0.0 || rng.unit() < (-worse / temperature).exp() {
+                order = new_order;
+                shifts = new_shifts;
+                score = new_score;
+                if score < best.1 {
+                    best = (ys, score);
+                }
+            }
+        }
+        best.0
     }
-    res
 }
 
-fn swap_two(init: &[LayoutAnnotation], first: usize) -> Vec<LayoutAnnotation> {
-    let mut res = Vec::<LayoutAnnotation>::new();
-    for i in 0..first {
-        res.push(init[i].clone());
+/// Xorshift generator, seeded for layouts to be the same from a frame to the next
+struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
     }
 
-    res.push(init[first+1].clone());
-    res[first].y = init[first].y;
-    res.push(init[first].clone());
-    res[first+1].y = init[first+1].y;
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
 
-    for i in first+2..init.len() {
-        res.push(init[i].clone());
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
     }
-    res
 }
 
-fn evaluate_move(annotations: &[LayoutAnnotation]) -> u32 {
-    let mut score: u32 = 0;
-    for i in 0..annotations.len()-1 {
-        for j in i+1..annotations.len() {
-            score += LayoutAnnotation::get_overlap_score(&annotations[i], &annotations[j]);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn annotation(id: u32, x: u32, y: u32) -> Annotation {
+        Annotation::new(id, Kind::Annotation, vec![format!("Unit {}", id)], geometry::Point{x, y})
+    }
+
+    fn assert_apart(annotations: &[Annotation]) {
+        for (i, a) in annotations.iter().enumerate() {
+            for b in annotations[i+1..].iter().filter(|b| b.side == a.side) {
+                let a_box = geometry::Rect{x:a.pos.x, y:a.pos.y, w:a.w + 1, h:a.h};
+                let b_box = geometry::Rect{x:b.pos.x, y:b.pos.y, w:b.w + 1, h:b.h};
+                assert!(!a_box.intersects(&b_box), "boxes {} and {} overlap", a.id, b.id);
+            }
+        }
+    }
+
+    #[test]
+    fn no_annotation() {
+        let gutters = place(&mut [], &[], &mut LayoutState::new(), geometry::Point{x:0, y:0}, 10, 10);
+        assert_eq!(gutters, Gutters::default());
+    }
+
+    #[test]
+    fn single_annotation() {
+        let mut annotations = [annotation(1, 2, 4)];
+        let gutters = place(&mut annotations, &[], &mut LayoutState::new(), geometry::Point{x:12, y:0}, 10, 10);
+        assert_eq!(gutters, Gutters{left:11, right:0, top:0, bottom:0});
+        assert_eq!(annotations[0].pos, geometry::Point{x:1, y:3});
+        assert_eq!(annotations[0].target_pos, geometry::Point{x:14, y:4});
+    }
+
+    #[test]
+    fn connectors_do_not_cross() {
+        // The farther target is lower: its box goes under the nearer one
+        let mut annotations = [annotation(1, 1, 3), annotation(2, 3, 4)];
+        place(&mut annotations, &[], &mut LayoutState::new(), geometry::Point{x:12, y:0}, 10, 10);
+        assert_apart(&annotations);
+        let near = annotations.iter().find(|a| a.id == 1).unwrap();
+        let far = annotations.iter().find(|a| a.id == 2).unwrap();
+        assert!(near.pos.y < far.pos.y);
+    }
+
+    #[test]
+    fn connectors_avoid_glyphs() {
+        let mut annotations = [annotation(1, 4, 5)];
+        let obstacles = [geometry::Point{x:2, y:5}];
+        place(&mut annotations, &obstacles, &mut LayoutState::new(), geometry::Point{x:12, y:0}, 10, 10);
+        let anchor_y = annotations[0].pos.y + 1;
+        assert!(anchor_y != 5);
+    }
+
+    #[test]
+    fn many_annotations_are_annealed() {
+        let mut annotations: Vec<Annotation> = (0..8).map(|i| annotation(i, i % 4, (i * 5) % 24)).collect();
+        let gutters = place(&mut annotations, &[], &mut LayoutState::new(), geometry::Point{x:12, y:0}, 10, 24);
+        assert_eq!(gutters.top, 0);
+        assert_eq!(gutters.bottom, 0);
+        assert_apart(&annotations);
+        assert!(annotations.iter().all(|a| a.pos.y + a.h <= 24));
+    }
+
+    #[test]
+    fn taller_boxes_are_stacked_apart() {
+        let lines = |id: u32| vec![format!("Unit {}", id), "AP 4".to_string(), "Overwatch".to_string()];
+        let mut annotations = [Annotation::new(1, Kind::Details, lines(1), geometry::Point{x:1, y:2}), annotation(2, 2, 3),
+            Annotation::new(3, Kind::Details, lines(3), geometry::Point{x:3, y:4})];
+        assert_eq!((annotations[0].w, annotations[0].h), (11, 5));
+        let gutters = place(&mut annotations, &[], &mut LayoutState::new(), geometry::Point{x:14, y:0}, 10, 14);
+        assert_eq!(gutters.left, 14);
+        assert_apart(&annotations);
+        assert!(annotations.iter().all(|a| a.side == Side::Left && a.pos.y + a.h <= 14));
+    }
+
+    #[test]
+    fn boxes_stay_while_units_move() {
+        let mut state = LayoutState::new();
+        let mut annotations = [annotation(1, 1, 3), annotation(2, 3, 8)];
+        place(&mut annotations, &[], &mut state, geometry::Point{x:12, y:0}, 10, 12);
+        let before: Vec<geometry::Point> = annotations.iter().map(|a| a.pos).collect();
+
+        let mut annotations = [annotation(1, 1, 4), annotation(2, 3, 7)];
+        place(&mut annotations, &[], &mut state, geometry::Point{x:12, y:0}, 10, 12);
+        let after: Vec<geometry::Point> = annotations.iter().map(|a| a.pos).collect();
+        assert_eq!(before, after);
+
+        // Far enough, the layout is optimized again
+        let mut annotations = [annotation(1, 1, 11), annotation(2, 3, 0)];
+        place(&mut annotations, &[], &mut state, geometry::Point{x:12, y:0}, 10, 12);
+        assert_eq!(annotations[0].id, 2);
+        assert_eq!(annotations[0].pos.y, 0);
+    }
+
+    #[test]
+    fn annotation_and_details_of_a_unit_keep_their_own_place() {
+        let mut annotations = [annotation(1, 1, 3), Annotation::new(1, Kind::Details, vec!["Unit 1".to_string(), "4 AP".to_string()],
+            geometry::Point{x:1, y:3})];
+        let mut state = LayoutState::new();
+        place(&mut annotations, &[], &mut state, geometry::Point{x:12, y:0}, 10, 12);
+        assert_apart(&annotations);
+        for a in annotations.iter() {
+            assert_eq!(state.placements.get(&a.key()), Some(&(a.side, a.pos.y)));
         }
     }
-    score
+
+    #[test]
+    fn overflow_from_short_boards_does_not_underflow() {
+        let mut annotations: Vec<Annotation> = (0..4).map(|id| annotation(id, 0, 5)).collect();
+        place(&mut annotations, &[], &mut LayoutState::new(), geometry::Point{x:12, y:0}, 4, 2);
+        assert!(annotations.iter().any(|a| a.side == Side::Top || a.side == Side::Bottom));
+    }
 }
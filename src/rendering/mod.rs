@@ -1,4 +1,9 @@
+#[allow(clippy::module_inception)]
 pub mod rendering;
+pub mod viewport;
+pub mod theme;
+pub mod animation;
 mod canvas;
 mod layout;
-mod geometry;
+mod router;
+pub mod geometry;
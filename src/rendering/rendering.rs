@@ -1,39 +1,295 @@
 
 use std::cmp;
+use std::collections::HashMap;
+use std::iter;
+use tui::style::{Color, Modifier, Style};
+use tui::text::Spans;
 
 use crate::graphics;
+use super::animation;
 use super::canvas;
+use super::geometry;
 use super::layout;
+use super::router;
+use super::theme;
+use super::viewport;
 
-pub fn render(visuals: &[graphics::Visual], texts: &[graphics::Text]) -> String {
+/// Smallest board part worth showing
+const MIN_VIEW: u32 = 5;
+/// Extra routing cost of a connector drawn over a floor tile
+const FLOOR_COST: u32 = 4;
+/// Content of the board cells under a tile label, above any visual content
+const LABELLED: u32 = u32::MAX;
+/// Extra routing cost of a connector drawn over a tile label
+const LABEL_COST: u32 = 12;
+
+/// What rendering the board keeps from a frame to the next
+pub struct BoardState {
+    pub camera: viewport::Camera,
+    /// Whether visuals of the overlay layer are drawn
+    pub overlays: bool,
+    /// Drawn over the visuals until they are over
+    pub animations: animation::Animations,
+    layout: layout::LayoutState,
+    /// Connector routes, kept while boxes and what lies under the connectors stay put
+    routes: router::Routes,
+}
+
+impl Default for BoardState {
+    fn default() -> BoardState {
+        BoardState::new()
+    }
+}
+
+impl BoardState {
+    pub fn new() -> BoardState {
+        BoardState{camera:viewport::Camera::new(), overlays:true, animations:animation::Animations::new(),
+            layout:layout::LayoutState::new(), routes:router::Routes::new()}
+    }
+}
+
+/// Render the board part seen by the camera of `state` within `max_w` x `max_h` characters,
+/// `None` if that is too small. The focused unit is highlighted.
+pub fn render(visuals: &[graphics::Visual], texts: &[graphics::Text], theme: &theme::Theme,
+              state: &mut BoardState, max_w: u32, max_h: u32, focus: Option<u32>) -> Option<Vec<Spans<'static>>> {
     let (w, h) = layout::get_span(visuals);
-    let text_len = layout::get_max_text_len(texts);
-    let vx = text_len + 10;
-    let vy = 5;
-    let mut canvas = canvas::Canvas::new(vx + w, vy + h);
+    let tw = theme.tile_width();
+    let focus_pos = focus.and_then(|id| visuals.iter().rev().find(|v| v.id == Some(id))).map(|v| (v.x, v.y));
+    let titles: Vec<&String> = texts.iter().filter_map(|t| match t {
+        graphics::Text::Title(s) => Some(s),
+        _ => None,
+    }).collect();
+    let title_h = titles.len() as u32;
+    let effects = state.animations.get_visuals();
 
+    // Gutters only grow until the annotations of the visible units fit. Attempts place boxes on a copy of the layout
+    // state, kept once they fit.
+    let mut gutters = layout::Gutters::default();
+    let mut placements = state.layout.clone();
+    let (view, visuals, annotations) = loop {
+        if max_w < gutters.width() + cmp::min(w, MIN_VIEW) * tw || max_h < title_h + gutters.height() + cmp::min(h, MIN_VIEW) {
+            return None;
+        }
 
-    // Visuals
-    for v in visuals.iter() {
-        canvas.draw_char(vx + v.x, vy + v.y, render_img(v.content));
+        // Visuals in canvas columns from here, `tw` per tile, from the lowest layer up
+        let view = state.camera.update(w, h, (max_w - gutters.width()) / tw, max_h - title_h - gutters.height(), focus_pos);
+        let mut in_view: Vec<graphics::Visual> = visuals.iter().chain(effects.iter())
+            .filter(|v| view.contains(v.x, v.y) && (state.overlays || v.layer != graphics::Layer::Overlay))
+            .map(|v| graphics::Visual{content:v.content, x:(v.x - view.x) * tw, y:v.y - view.y, id:v.id, color:v.color, layer:v.layer})
+            .collect();
+        in_view.sort_by_key(|v| v.layer);
+        let mut annotations = layout::collect(&in_view, texts);
+        let obstacles = get_obstacles(&in_view);
+        let origin = geometry::Point{x:gutters.left, y:title_h + gutters.top};
+        let needed = layout::place(&mut annotations, &obstacles, &mut placements, origin, view.w * tw, view.h);
+        if gutters.contains(&needed) {
+            break (view, in_view, annotations);
+        }
+        gutters = gutters.max(&needed);
+    };
+    state.layout = placements;
+    let (vx, vy) = (gutters.left, title_h + gutters.top);
+    let title_w = titles.iter().map(|t| cmp::min(t.chars().count() as u32, max_w)).max().unwrap_or(0);
+    let canvas_w = annotations.iter().map(|a| a.pos.x + a.w + 1).fold(cmp::max(title_w, gutters.width() + view.w * tw), cmp::max);
+    let canvas_h = annotations.iter().map(|a| a.pos.y + a.h).fold(title_h + gutters.height() + view.h, cmp::max);
+    let mut canvas = canvas::Canvas::new(canvas_w, canvas_h);
+
+    // Titles
+    for (y, title) in titles.iter().enumerate() {
+        canvas.draw_styled_string(0, y as u32, title, Style::default().add_modifier(Modifier::BOLD));
+    }
+
+    // Visuals, effects excepted
+    for v in visuals.iter().filter(|v| v.layer != graphics::Layer::Effects) {
+        draw_visual(&mut canvas, theme, v, vx, vy, focus);
+    }
+
+    // Tile labels, over walls if possible and never over units
+    let mut contents = HashMap::<(u32, u32), u32>::new();
+    for v in visuals.iter().filter(|v| v.layer == graphics::Layer::Floor || v.layer == graphics::Layer::Units) {
+        for x in vx + v.x..vx + v.x + tw {
+            let content = contents.entry((x, vy + v.y)).or_insert(0);
+            *content = cmp::max(*content, v.content);
+        }
+    }
+    let mut labelled = Vec::<geometry::Point>::new();
+    for t in texts.iter() {
+        if let graphics::Text::TileLabel(x, y, s) = t {
+            if !view.contains(*x, *y) {
+                continue;
+            }
+            let tile = geometry::Point{x:vx + (x - view.x) * tw, y:vy + y - view.y};
+            let start = match place_label(&contents, &tile, tw, s.chars().count() as u32) {
+                Some(start) => start,
+                None => continue,
+            };
+            for (i, c) in s.chars().enumerate() {
+                let p = geometry::Point{x:start.x + i as u32, y:start.y};
+                // No other label over this one
+                contents.insert((p.x, p.y), LABELLED);
+                canvas.draw_styled_char(p.x, p.y, c, Style::default().fg(Color::Cyan));
+                labelled.push(p);
+            }
+        }
     }
 
     // Texts
-    let mut annotations = layout::collect(visuals, texts);
-    layout::place(&mut annotations);
     for a in annotations.iter() {
-        canvas.draw_string(a.pos.x+1, a.pos.y+1, &a.text);
         canvas.draw_box(a.pos.x, a.pos.y, a.w, a.h);
-        canvas.draw_connector(a.w, a.pos.y+1, vx+a.target_pos.x, vy+a.target_pos.y);
+        for (i, line) in a.lines.iter().enumerate() {
+            canvas.draw_string(a.pos.x+1, a.pos.y+1+i as u32, line);
+        }
+    }
+
+    // Connectors, shortest first, around boxes and units and over as few floor tiles as possible
+    let mut obstacles = router::Obstacles{w:canvas_w, h:canvas_h, blocked:annotations.iter().map(|a| a.get_rect()).collect(), costs:vec![]};
+    for v in visuals.iter() {
+        let tile = geometry::Rect{x:vx + v.x, y:vy + v.y, w:tw, h:1};
+        match v.layer {
+            graphics::Layer::Floor if v.content > 0 => {
+                obstacles.costs.extend((tile.x..tile.x + tw).map(|x| (geometry::Point{x, y:tile.y}, FLOOR_COST)));
+            }
+            graphics::Layer::Units => obstacles.blocked.push(tile),
+            _ => {}
+        }
+    }
+    obstacles.costs.extend(labelled.iter().map(|p| (*p, LABEL_COST)));
+    let mut order: Vec<&layout::Annotation> = annotations.iter().collect();
+    order.sort_by_key(|a| a.get_exit().1.manhattan(&a.target_pos));
+    let connectors = order.iter().map(|a| {
+        let (previous, from) = a.get_exit();
+        router::Connector{previous, from, goal:geometry::Rect{x:a.target_pos.x, y:a.target_pos.y, w:tw, h:1}}
+    }).collect();
+    let routes = state.routes.route(obstacles, connectors);
+    for (a, route) in order.iter().zip(routes.iter()) {
+        let cells = match route {
+            Some(route) => iter::once(a.get_exit().0).chain(route.iter().cloned()).collect(),
+            None => get_cells(&a.get_path()),
+        };
+        let cells: Vec<(u32, u32)> = cells.iter().map(|p| (p.x, p.y)).collect();
+        canvas.draw_line(&cells);
+    }
+
+    // Effects, over connectors
+    for v in visuals.iter().filter(|v| v.layer == graphics::Layer::Effects) {
+        draw_visual(&mut canvas, theme, v, vx, vy, focus);
     }
 
-    canvas.to_string()
+    // Popups, floating over anything else above their square, or under it right under the titles
+    for t in texts.iter() {
+        if let graphics::Text::Popup(x, y, s) = t {
+            if !view.contains(*x, *y) {
+                continue;
+            }
+            let len = s.chars().count() as u32;
+            let (px, py) = (vx + (x - view.x) * tw, vy + y - view.y);
+            let px = cmp::min((px + tw / 2).saturating_sub(len / 2), canvas_w.saturating_sub(len));
+            let py = if py > title_h { py - 1 } else { py + 1 };
+            canvas.draw_styled_string(px, py, s, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+        }
+    }
+
+    if theme.ascii_lines {
+        canvas.use_ascii_lines();
+    }
+    Some(canvas.to_spans())
+}
+
+/// Draw a visual of the board drawn at `vx`, `vy`, overlays and cursors changing the style of what is under them
+fn draw_visual(canvas: &mut canvas::Canvas, theme: &theme::Theme, v: &graphics::Visual, vx: u32, vy: u32, focus: Option<u32>) {
+    let glyph = theme.get_glyph(v.content);
+    let chars = if theme.double_width { glyph.wide.to_vec() } else { vec![glyph.narrow] };
+    for (x, c) in (vx + v.x..).zip(chars) {
+        match v.layer {
+            graphics::Layer::Overlay => {
+                if let Some(color) = glyph.color {
+                    canvas.patch_style(x, vy + v.y, Style::default().bg(color));
+                }
+            }
+            graphics::Layer::Cursor => {
+                if c != ' ' {
+                    canvas.draw_over(x, vy + v.y, c, theme.get_style(v));
+                }
+                canvas.patch_style(x, vy + v.y, Style::default().add_modifier(Modifier::REVERSED));
+            }
+            _ => {
+                let mut style = theme.get_style(v);
+                if focus.is_some() && v.id == focus {
+                    style = style.add_modifier(Modifier::REVERSED | Modifier::BOLD);
+                }
+                canvas.draw_over(x, vy + v.y, c, style);
+            }
+        }
+    }
+}
+
+/// Squares the layout keeps connectors off: units only. Walls are drawn blank, and crossing floor squares is left
+/// to the router, which only makes it costlier.
+fn get_obstacles(visuals: &[graphics::Visual]) -> Vec<geometry::Point> {
+    visuals.iter()
+        .filter(|v| v.layer == graphics::Layer::Units)
+        .map(|v| geometry::Point{x:v.x, y:v.y})
+        .collect()
+}
+
+/// Where a label of `len` columns starts beside the `tw` columns of a tile: right of it, left of it, above or under
+/// it, the first one over walls only, else the first one over walls and floor, `None` if all cover a unit, another
+/// label or a gutter
+fn place_label(contents: &HashMap<(u32, u32), u32>, tile: &geometry::Point, tw: u32, len: u32) -> Option<geometry::Point> {
+    let mut candidates = vec![geometry::Point{x:tile.x + tw, y:tile.y}];
+    if tile.x >= len {
+        candidates.push(geometry::Point{x:tile.x - len, y:tile.y});
+    }
+    if tile.y > 0 {
+        candidates.push(geometry::Point{x:tile.x, y:tile.y - 1});
+    }
+    candidates.push(geometry::Point{x:tile.x, y:tile.y + 1});
+    let over = |p: &geometry::Point, max: u32| (p.x..p.x + len).all(|x| contents.get(&(x, p.y)).is_some_and(|c| *c <= max));
+    candidates.iter().find(|p| over(p, 0)).or_else(|| candidates.iter().find(|p| over(p, 1))).cloned()
+}
+
+/// Cells from a point to the next, the points being on a same row or column
+fn get_cells(points: &[geometry::Point]) -> Vec<geometry::Point> {
+    let mut cells = points[..1].to_vec();
+    for pair in points.windows(2) {
+        let mut segment = geometry::Segment::new(pair[0], pair[1]).points();
+        if segment[0] != pair[0] {
+            segment.reverse();
+        }
+        cells.extend_from_slice(&segment[1..]);
+    }
+    cells
 }
 
-fn render_img(img: u32) -> char {
-    let imgs = vec![' ', '□', '△', '▽', '>', '<'];
-    match imgs.get(img as usize) {
-        Some(c) => *c,
-        None => 'X',
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Contents of a row of `tiles`, one column each and starting at column `x`
+    fn row(x: u32, tiles: &[u32]) -> HashMap<(u32, u32), u32> {
+        tiles.iter().enumerate().map(|(i, c)| ((x + i as u32, 0), *c)).collect()
+    }
+
+    #[test]
+    fn labels_go_over_walls_then_floor() {
+        let tile = geometry::Point{x:3, y:0};
+        assert_eq!(place_label(&row(0, &[0, 0, 0, 2, 1, 1]), &tile, 1, 2), Some(geometry::Point{x:1, y:0}));
+        assert_eq!(place_label(&row(0, &[1, 1, 0, 2, 1, 1]), &tile, 1, 2), Some(geometry::Point{x:4, y:0}));
+    }
+
+    #[test]
+    fn labels_stay_off_units_labels_and_gutters() {
+        let tile = geometry::Point{x:3, y:0};
+        assert_eq!(place_label(&row(0, &[1, 1, 1, 2, 1, 2]), &tile, 1, 2), Some(geometry::Point{x:1, y:0}));
+        assert_eq!(place_label(&row(0, &[LABELLED, 1, 1, 2, 1, LABELLED]), &tile, 1, 2), Some(geometry::Point{x:1, y:0}));
+        assert_eq!(place_label(&row(2, &[1, 2, 1]), &tile, 1, 2), None);
+    }
+
+    #[test]
+    fn only_units_are_obstacles() {
+        let visual = |content, x, layer| graphics::Visual{content, x, y:0, id:None, color:graphics::Color::Default, layer};
+        let visuals = [visual(0, 0, graphics::Layer::Floor), visual(1, 1, graphics::Layer::Floor), visual(1, 2, graphics::Layer::Floor),
+            visual(4, 2, graphics::Layer::Units), visual(11, 1, graphics::Layer::Overlay), visual(17, 1, graphics::Layer::Effects)];
+        assert_eq!(get_obstacles(&visuals), vec![geometry::Point{x:2, y:0}]);
     }
 }
@@ -0,0 +1,368 @@
+
+use std::cmp;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::geometry;
+
+/// Extra cost of a step changing direction
+const TURN_COST: u32 = 2;
+/// Extra cost of a step crossing another connector
+const CROSS_COST: u32 = 3;
+const BLOCKED: u32 = u32::MAX;
+/// Weight of the distance left in the A* estimate, trading the shortest route for a faster search
+const ESTIMATE_WEIGHT: u32 = 3;
+/// States explored before giving up on a route
+const MAX_EXPANSIONS: u32 = 1500;
+/// Cells a route may stray out of the rectangle between its ends
+const DETOUR: u32 = 4;
+
+/// Up, down, left, right
+const STEPS: [(i32, i32); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+
+/// Finds orthogonal connector routes around boxes and tiles, crossing earlier routes only at right angles
+pub struct Router {
+    w: u32,
+    h: u32,
+    /// Extra cost of entering each cell, `BLOCKED` for boxes and units
+    costs: Vec<u32>,
+    /// Whether earlier routes go through each cell horizontally and vertically
+    lines: Vec<(bool, bool)>,
+    /// Search state by cell and direction, reset after each route
+    spent: Vec<u32>,
+    came_from: Vec<usize>,
+}
+
+impl Router {
+    pub fn new(w: u32, h: u32) -> Router {
+        let size = (w * h) as usize;
+        Router{w, h, costs:vec![0; size], lines:vec![(false, false); size],
+            spent:vec![u32::MAX; size * STEPS.len()], came_from:vec![usize::MAX; size * STEPS.len()]}
+    }
+
+    /// Forget all costs and routes for a `w` x `h` canvas, keeping the buffers
+    pub fn reset(&mut self, w: u32, h: u32) {
+        let size = (w * h) as usize;
+        self.w = w;
+        self.h = h;
+        self.costs.clear();
+        self.costs.resize(size, 0);
+        self.lines.clear();
+        self.lines.resize(size, (false, false));
+        // The search state is already reset after each route, only new cells need it
+        self.spent.resize(size * STEPS.len(), u32::MAX);
+        self.came_from.resize(size * STEPS.len(), usize::MAX);
+    }
+
+    fn index(&self, p: &geometry::Point) -> Option<usize> {
+        if p.x < self.w && p.y < self.h {
+            Some((p.y * self.w + p.x) as usize)
+        }
+        else {
+            None
+        }
+    }
+
+    pub fn block(&mut self, rect: &geometry::Rect) {
+        for y in rect.y..rect.y + rect.h {
+            for x in rect.x..rect.x + rect.w {
+                if let Some(i) = self.index(&geometry::Point{x, y}) {
+                    self.costs[i] = BLOCKED;
+                }
+            }
+        }
+    }
+
+    pub fn add_cost(&mut self, p: &geometry::Point, cost: u32) {
+        if let Some(i) = self.index(p) {
+            self.costs[i] = self.costs[i].saturating_add(cost);
+        }
+    }
+
+    /// Cells of a route leaving `from` in the direction away from `previous`, up to a cell of `goal`
+    pub fn route(&mut self, previous: &geometry::Point, from: &geometry::Point, goal: &geometry::Rect) -> Option<Vec<geometry::Point>> {
+        let start = self.index(from)?;
+        if self.costs[start] == BLOCKED {
+            return None;
+        }
+        let first = STEPS.iter().position(|(dx, dy)| {
+            previous.x as i32 + dx == from.x as i32 && previous.y as i32 + dy == from.y as i32
+        })?;
+
+        // A* over the cells and directions of the area around both ends
+        let left = cmp::min(from.x, goal.x).saturating_sub(DETOUR);
+        let top = cmp::min(from.y, goal.y).saturating_sub(DETOUR);
+        let area = geometry::Rect{x:left, y:top,
+            w:cmp::min(cmp::max(from.x + 1, goal.x + goal.w) + DETOUR, self.w) - left,
+            h:cmp::min(cmp::max(from.y + 1, goal.y + goal.h) + DETOUR, self.h) - top};
+        let estimate = |p: &geometry::Point| {
+            let x = if p.x < goal.x { goal.x - p.x } else { p.x.saturating_sub(goal.x + goal.w - 1) };
+            let y = if p.y < goal.y { goal.y - p.y } else { p.y.saturating_sub(goal.y + goal.h - 1) };
+            ESTIMATE_WEIGHT * (x + y)
+        };
+        let mut open = BinaryHeap::new();
+        let mut touched = vec![start * 4 + first];
+        self.spent[start * 4 + first] = 0;
+        open.push(Reverse((estimate(from), 0, start * 4 + first)));
+        let route = self.search(&mut open, &mut touched, &area, goal, &estimate);
+        for state in touched {
+            self.spent[state] = u32::MAX;
+            self.came_from[state] = usize::MAX;
+        }
+        route
+    }
+
+    fn search(&mut self, open: &mut BinaryHeap<Reverse<(u32, u32, usize)>>, touched: &mut Vec<usize>, area: &geometry::Rect,
+              goal: &geometry::Rect, estimate: &dyn Fn(&geometry::Point) -> u32) -> Option<Vec<geometry::Point>> {
+
+        let mut expansions = 0;
+        while let Some(Reverse((_, cost, state))) = open.pop() {
+            if cost > self.spent[state] {
+                continue;
+            }
+            expansions += 1;
+            if expansions > MAX_EXPANSIONS {
+                return None;
+            }
+            let (cell, dir) = (state / 4, state % 4);
+            let p = geometry::Point{x:cell as u32 % self.w, y:cell as u32 / self.w};
+            if goal.contains(&p) {
+                return Some(self.get_route(state));
+            }
+            let (horizontal, vertical) = self.lines[cell];
+            for (next_dir, (dx, dy)) in STEPS.iter().enumerate() {
+                // No going back, no turning where another route passes
+                if next_dir ^ 1 == dir || (next_dir != dir && (horizontal || vertical)) {
+                    continue;
+                }
+                let (x, y) = (p.x as i32 + dx, p.y as i32 + dy);
+                if x < 0 || y < 0 {
+                    continue;
+                }
+                let next = geometry::Point{x:x as u32, y:y as u32};
+                if !area.contains(&next) {
+                    continue;
+                }
+                let next_cell = self.index(&next)?;
+                let mut step = 1;
+                if !goal.contains(&next) {
+                    // Other routes are only crossed at right angles
+                    let (h, v) = self.lines[next_cell];
+                    let along = if next_dir < 2 { v } else { h };
+                    if self.costs[next_cell] == BLOCKED || along {
+                        continue;
+                    }
+                    step += self.costs[next_cell];
+                    if h || v {
+                        step += CROSS_COST;
+                    }
+                }
+                if next_dir != dir {
+                    step += TURN_COST;
+                }
+                let next_state = next_cell * 4 + next_dir;
+                if cost + step < self.spent[next_state] {
+                    self.spent[next_state] = cost + step;
+                    self.came_from[next_state] = state;
+                    touched.push(next_state);
+                    open.push(Reverse((cost + step + estimate(&next), cost + step, next_state)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Cells leading to the `end` state of the search
+    fn get_route(&self, end: usize) -> Vec<geometry::Point> {
+        let mut route = vec![];
+        let mut state = end;
+        while state != usize::MAX {
+            let cell = (state / 4) as u32;
+            route.push(geometry::Point{x:cell % self.w, y:cell / self.w});
+            state = self.came_from[state];
+        }
+        route.reverse();
+        route
+    }
+
+    /// Whether `route` could still be found: clear of blocked cells up to `goal`, crossing earlier routes only at
+    /// right angles and never turning on them
+    fn is_clear(&self, route: &[geometry::Point], goal: &geometry::Rect) -> bool {
+        let mut dir = None;
+        for pair in route.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let next_dir = match STEPS.iter().position(|(dx, dy)| a.x as i32 + dx == b.x as i32 && a.y as i32 + dy == b.y as i32) {
+                Some(next_dir) => next_dir,
+                None => return false,
+            };
+            let (cell, next_cell) = match (self.index(&a), self.index(&b)) {
+                (Some(cell), Some(next_cell)) => (cell, next_cell),
+                _ => return false,
+            };
+            let (horizontal, vertical) = self.lines[cell];
+            if dir.is_some_and(|dir| dir != next_dir) && (horizontal || vertical) {
+                return false;
+            }
+            if !goal.contains(&b) {
+                let (h, v) = self.lines[next_cell];
+                if self.costs[next_cell] == BLOCKED || (if next_dir < 2 { v } else { h }) {
+                    return false;
+                }
+            }
+            dir = Some(next_dir);
+        }
+        true
+    }
+
+    /// Mark the cells of a route, for the next ones to cross it only at right angles
+    pub fn add(&mut self, cells: &[geometry::Point]) {
+        for pair in cells.windows(2) {
+            let horizontal = pair[0].y == pair[1].y;
+            for p in pair.iter() {
+                if let Some(i) = self.index(p) {
+                    if horizontal {
+                        self.lines[i].0 = true;
+                    }
+                    else {
+                        self.lines[i].1 = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// What connectors go around on a `w` x `h` canvas: blocked rectangles, and cells costlier to cross
+#[derive(Clone, PartialEq, Default)]
+pub struct Obstacles {
+    pub w: u32,
+    pub h: u32,
+    pub blocked: Vec<geometry::Rect>,
+    pub costs: Vec<(geometry::Point, u32)>,
+}
+
+/// A connector leaving `from` in the direction away from `previous`, up to a cell of `goal`
+#[derive(Clone, PartialEq)]
+pub struct Connector {
+    pub previous: geometry::Point,
+    pub from: geometry::Point,
+    pub goal: geometry::Rect,
+}
+
+/// Routes of the last frame and the router that found them. Nothing is routed again while nothing changed, and
+/// connectors that did not move keep their route while it stays clear.
+pub struct Routes {
+    router: Router,
+    obstacles: Obstacles,
+    connectors: Vec<Connector>,
+    routes: Vec<Option<Vec<geometry::Point>>>,
+}
+
+impl Default for Routes {
+    fn default() -> Routes {
+        Routes::new()
+    }
+}
+
+impl Routes {
+    pub fn new() -> Routes {
+        Routes{router:Router::new(0, 0), obstacles:Obstacles::default(), connectors:vec![], routes:vec![]}
+    }
+
+    /// Route of each connector in turn, crossing earlier ones only at right angles, `None` where there is none
+    pub fn route(&mut self, obstacles: Obstacles, connectors: Vec<Connector>) -> &[Option<Vec<geometry::Point>>] {
+        if obstacles == self.obstacles && connectors == self.connectors {
+            return &self.routes;
+        }
+        self.router.reset(obstacles.w, obstacles.h);
+        for rect in obstacles.blocked.iter() {
+            self.router.block(rect);
+        }
+        for (p, cost) in obstacles.costs.iter() {
+            self.router.add_cost(p, *cost);
+        }
+        let mut last: HashMap<(geometry::Point, geometry::Point), (geometry::Rect, Vec<geometry::Point>)> = HashMap::new();
+        for (c, route) in self.connectors.iter().zip(self.routes.drain(..)) {
+            if let Some(route) = route {
+                last.insert((c.previous, c.from), (c.goal, route));
+            }
+        }
+        for c in connectors.iter() {
+            let kept = last.remove(&(c.previous, c.from))
+                .filter(|(goal, route)| *goal == c.goal && self.router.is_clear(route, goal))
+                .map(|(_, route)| route);
+            let route = kept.or_else(|| self.router.route(&c.previous, &c.from, &c.goal));
+            if let Some(route) = &route {
+                self.router.add(&route[..route.len() - 1]);
+            }
+            self.routes.push(route);
+        }
+        self.obstacles = obstacles;
+        self.connectors = connectors;
+        &self.routes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geometry::{Point, Rect};
+
+    fn p(x: u32, y: u32) -> Point {
+        Point{x, y}
+    }
+
+    #[test]
+    fn routes_around_blocked_cells() {
+        let mut router = Router::new(20, 10);
+        router.block(&Rect{x:5, y:0, w:2, h:6});
+        let route = router.route(&p(0, 2), &p(1, 2), &Rect{x:10, y:2, w:1, h:1}).unwrap();
+        assert_eq!(route.first(), Some(&p(1, 2)));
+        assert_eq!(route.last(), Some(&p(10, 2)));
+        assert!(route.iter().all(|c| !(c.x >= 5 && c.x < 7 && c.y < 6)));
+        assert!(route.windows(2).all(|w| w[0].manhattan(&w[1]) == 1));
+    }
+
+    #[test]
+    fn crosses_other_routes_at_right_angles() {
+        let mut router = Router::new(20, 10);
+        let wall: Vec<Point> = (0..10).map(|y| p(8, y)).collect();
+        router.add(&wall);
+        let route = router.route(&p(0, 4), &p(1, 4), &Rect{x:15, y:6, w:1, h:1}).unwrap();
+        let crossing = route.iter().position(|c| c.x == 8).unwrap();
+        assert_eq!(route[crossing - 1].y, route[crossing].y);
+        assert_eq!(route[crossing + 1].y, route[crossing].y);
+        assert_eq!(route.iter().filter(|c| c.x == 8).count(), 1);
+    }
+
+    #[test]
+    fn no_route_out_of_a_closed_area() {
+        let mut router = Router::new(20, 10);
+        router.block(&Rect{x:4, y:0, w:1, h:10});
+        assert_eq!(router.route(&p(0, 4), &p(1, 4), &Rect{x:10, y:4, w:1, h:1}), None);
+        // The search state is reset for the next route
+        assert!(router.route(&p(0, 4), &p(1, 4), &Rect{x:3, y:4, w:1, h:1}).is_some());
+    }
+
+    #[test]
+    fn reset_routers_route_like_new_ones() {
+        let mut router = Router::new(30, 12);
+        router.block(&Rect{x:5, y:0, w:2, h:10});
+        router.route(&p(0, 2), &p(1, 2), &Rect{x:10, y:2, w:1, h:1});
+        router.reset(20, 10);
+        assert_eq!(router.route(&p(0, 4), &p(1, 4), &Rect{x:10, y:4, w:1, h:1}), Some((1..11).map(|x| p(x, 4)).collect()));
+    }
+
+    #[test]
+    fn routes_follow_their_obstacles() {
+        let obstacles = |blocked| Obstacles{w:20, h:10, blocked, costs:vec![]};
+        let connectors = vec![Connector{previous:p(0, 4), from:p(1, 4), goal:Rect{x:10, y:4, w:1, h:1}}];
+        let mut routes = Routes::new();
+        let straight = routes.route(obstacles(vec![]), connectors.clone()).to_vec();
+        assert_eq!(straight[0].as_ref().map(|r| r.len()), Some(10));
+        assert_eq!(routes.route(obstacles(vec![]), connectors.clone()), straight.as_slice());
+        let around = routes.route(obstacles(vec![Rect{x:5, y:3, w:1, h:3}]), connectors).to_vec();
+        assert!(around[0].as_ref().unwrap().len() > 10);
+        assert!(around[0].as_ref().unwrap().iter().all(|c| !(c.x == 5 && c.y >= 3 && c.y < 6)));
+    }
+}
@@ -0,0 +1,303 @@
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use tui::style::{Color, Style};
+
+use crate::graphics;
+
+pub const DEFAULT_THEME_PATH: &str = "theme.json";
+
+/// How a visual content code is drawn
+#[derive(Clone)]
+pub struct Glyph {
+    pub narrow: char,
+    /// Both columns of the tile in double-width mode
+    pub wide: [char; 2],
+    pub color: Option<Color>,
+}
+
+impl Glyph {
+    fn new(narrow: char, wide: &str, color: Option<Color>) -> Glyph {
+        let mut chars = wide.chars();
+        let first = chars.next().unwrap_or(narrow);
+        Glyph{narrow, wide:[first, chars.next().unwrap_or(' ')], color}
+    }
+}
+
+/// Glyphs and colors of the board, indexed by visual content
+pub struct Theme {
+    pub glyphs: Vec<Glyph>,
+    /// Boxes and connectors drawn with `+`, `-` and `|`
+    pub ascii_lines: bool,
+    /// Two columns per tile, for squarer maps
+    pub double_width: bool,
+}
+
+/// Content of a theme file, e.g. `{"base": "ascii", "contents": {"6": {"glyph": "G", "color": "red"}}}`
+#[derive(Deserialize)]
+struct ThemeFile {
+    base: Option<String>,
+    #[serde(default)]
+    double_width: bool,
+    #[serde(default)]
+    contents: BTreeMap<u32, GlyphFile>,
+}
+
+#[derive(Deserialize)]
+struct GlyphFile {
+    glyph: Option<char>,
+    wide: Option<String>,
+    color: Option<String>,
+}
+
+impl Theme {
+    pub fn unicode() -> Theme {
+        let glyphs = vec![
+            Glyph::new(' ', "  ", None),
+            Glyph::new('□', "□ ", None),
+            Glyph::new('△', "△ ", None),
+            Glyph::new('▽', "▽ ", None),
+            Glyph::new('>', "═>", None),
+            Glyph::new('<', "<═", None),
+            Glyph::new('◆', "◆ ", Some(Color::LightMagenta)),
+            Glyph::new('◎', "◎ ", Some(Color::LightGreen)),
+            // Overlays, drawn as the background color of the squares: field of fire, overwatch, blip in sight
+            Glyph::new(' ', "  ", Some(Color::Blue)),
+            Glyph::new(' ', "  ", Some(Color::Red)),
+            Glyph::new(' ', "  ", None),
+            // Movement range with action points, then with command points, and planned path
+            Glyph::new(' ', "  ", Some(Color::Green)),
+            Glyph::new(' ', "  ", Some(Color::DarkGray)),
+            Glyph::new('↑', "↑ ", None),
+            Glyph::new('↓', "↓ ", None),
+            Glyph::new('→', "→ ", None),
+            Glyph::new('←', "← ", None),
+            // Animations: muzzle flash, flames, fading model
+            Glyph::new('✶', "✶ ", Some(Color::Yellow)),
+            Glyph::new('▓', "▓▓", Some(Color::LightRed)),
+            Glyph::new('▒', "▒▒", Some(Color::Gray)),
+            Glyph::new('░', "░░", Some(Color::DarkGray)),
+        ];
+        Theme{glyphs, ascii_lines:false, double_width:false}
+    }
+
+    /// For terminals lacking box-drawing and geometric characters
+    pub fn ascii() -> Theme {
+        let glyphs = vec![
+            Glyph::new(' ', "  ", None),
+            Glyph::new('.', ". ", None),
+            Glyph::new('^', "/\\", None),
+            Glyph::new('v', "\\/", None),
+            Glyph::new('>', "=>", None),
+            Glyph::new('<', "<=", None),
+            Glyph::new('g', "{}", Some(Color::LightMagenta)),
+            Glyph::new('?', "??", Some(Color::LightGreen)),
+            Glyph::new(' ', "  ", Some(Color::Blue)),
+            Glyph::new(' ', "  ", Some(Color::Red)),
+            Glyph::new(' ', "  ", None),
+            Glyph::new(' ', "  ", Some(Color::Green)),
+            Glyph::new(' ', "  ", Some(Color::DarkGray)),
+            Glyph::new('^', "^ ", None),
+            Glyph::new('v', "v ", None),
+            Glyph::new('>', "> ", None),
+            Glyph::new('<', "< ", None),
+            Glyph::new('*', "**", Some(Color::Yellow)),
+            Glyph::new('#', "##", Some(Color::LightRed)),
+            Glyph::new('%', "%%", Some(Color::Gray)),
+            Glyph::new(':', "::", Some(Color::DarkGray)),
+        ];
+        Theme{glyphs, ascii_lines:true, double_width:false}
+    }
+
+    /// Built-in theme by name: unicode or ascii
+    pub fn named(name: &str) -> Result<Theme, String> {
+        match name {
+            "unicode" => Ok(Theme::unicode()),
+            "ascii" => Ok(Theme::ascii()),
+            _ => Err(format!("unknown theme '{}', available: unicode, ascii", name)),
+        }
+    }
+
+    /// A built-in theme with the glyphs of the file replacing its own
+    pub fn load(path: &Path) -> Result<Theme, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let file: ThemeFile = serde_json::from_str(&content).map_err(|e| format!("invalid theme {}: {}", path.display(), e))?;
+
+        let mut theme = Theme::named(file.base.as_deref().unwrap_or("unicode"))?;
+        theme.double_width = file.double_width;
+        for (content, g) in file.contents.iter() {
+            let index = *content as usize;
+            if index >= theme.glyphs.len() {
+                theme.glyphs.resize(index + 1, Glyph::new('X', "XX", None));
+            }
+            let glyph = &mut theme.glyphs[index];
+            if let Some(c) = g.glyph {
+                *glyph = Glyph::new(c, &format!("{} ", c), glyph.color);
+            }
+            if let Some(wide) = &g.wide {
+                *glyph = Glyph::new(glyph.narrow, wide, glyph.color);
+            }
+            if let Some(color) = &g.color {
+                glyph.color = Some(parse_color(color)?);
+            }
+        }
+        Ok(theme)
+    }
+
+    /// The file at `path` if there is one, the built-in theme the terminal can draw otherwise
+    pub fn load_or_default(path: &Path) -> Result<Theme, String> {
+        if path.exists() {
+            Theme::load(path)
+        }
+        else {
+            Ok(Theme::for_terminal())
+        }
+    }
+
+    /// The unicode theme, or the ascii one when the locale or `TERM` tells unicode would not show
+    pub fn for_terminal() -> Theme {
+        let var = |name: &str| env::var(name).ok().filter(|v| !v.is_empty());
+        let locale = var("LC_ALL").or_else(|| var("LC_CTYPE")).or_else(|| var("LANG"));
+        if shows_unicode(var("TERM").as_deref(), locale.as_deref()) {
+            Theme::unicode()
+        }
+        else {
+            Theme::ascii()
+        }
+    }
+
+    /// Columns taken by a tile
+    pub fn tile_width(&self) -> u32 {
+        if self.double_width { 2 } else { 1 }
+    }
+
+    pub fn get_glyph(&self, content: u32) -> Glyph {
+        match self.glyphs.get(content as usize) {
+            Some(g) => g.clone(),
+            None => Glyph::new('X', "XX", None),
+        }
+    }
+
+    /// Squad color if any, otherwise the color of the content
+    pub fn get_style(&self, visual: &graphics::Visual) -> Style {
+        let color = match visual.color {
+            graphics::Color::Purple => Some(Color::Magenta),
+            graphics::Color::Yellow => Some(Color::Yellow),
+            graphics::Color::Grey => Some(Color::Gray),
+            graphics::Color::Blue => Some(Color::LightBlue),
+            graphics::Color::Green => Some(Color::Green),
+            graphics::Color::Red => Some(Color::Red),
+            graphics::Color::Orange => Some(Color::LightRed),
+            graphics::Color::Black => Some(Color::DarkGray),
+            graphics::Color::Default => self.get_glyph(visual.content).color,
+        };
+        match color {
+            Some(color) => Style::default().fg(color),
+            None => Style::default(),
+        }
+    }
+}
+
+/// Whether a terminal of type `term` under `locale` draws unicode: not the Linux console nor dumb terminals,
+/// and only with a UTF-8 locale
+fn shows_unicode(term: Option<&str>, locale: Option<&str>) -> bool {
+    if let Some("linux" | "dumb" | "vt100" | "vt220") = term {
+        return false;
+    }
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        }
+        None => false,
+    }
+}
+
+/// A color name as in `tui::style::Color`, e.g. `lightred`, or `#rrggbb`
+fn parse_color(s: &str) -> Result<Color, String> {
+    if let Some(hex) = s.strip_prefix('#') {
+        let value = u32::from_str_radix(hex, 16).map_err(|_| format!("invalid color '{}'", s))?;
+        if hex.len() != 6 {
+            return Err(format!("invalid color '{}'", s));
+        }
+        return Ok(Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8));
+    }
+    let color = match s.to_lowercase().replace('_', "").as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "lightred" => Color::LightRed,
+        "lightgreen" => Color::LightGreen,
+        "lightyellow" => Color::LightYellow,
+        "lightblue" => Color::LightBlue,
+        "lightmagenta" => Color::LightMagenta,
+        "lightcyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return Err(format!("invalid color '{}'", s)),
+    };
+    Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_needs_a_utf8_locale_and_a_capable_terminal() {
+        assert!(shows_unicode(Some("xterm-256color"), Some("en_GB.UTF-8")));
+        assert!(shows_unicode(None, Some("fr_FR.utf8")));
+        assert!(!shows_unicode(Some("xterm"), Some("C")));
+        assert!(!shows_unicode(Some("xterm"), None));
+        assert!(!shows_unicode(Some("linux"), Some("en_US.UTF-8")));
+        assert!(!shows_unicode(Some("dumb"), Some("en_US.UTF-8")));
+    }
+
+    #[test]
+    fn parses_color_names_and_hex() {
+        assert_eq!(parse_color("red"), Ok(Color::Red));
+        assert_eq!(parse_color("Light_Blue"), Ok(Color::LightBlue));
+        assert_eq!(parse_color("grey"), Ok(Color::Gray));
+        assert_eq!(parse_color("#ff8000"), Ok(Color::Rgb(255, 128, 0)));
+        assert!(parse_color("#ff80").is_err());
+        assert!(parse_color("#gg8000").is_err());
+        assert!(parse_color("mauve").is_err());
+    }
+
+    fn load(name: &str, content: &str) -> Result<Theme, String> {
+        let path = env::temp_dir().join(format!("shulk-test-{}-{}.json", name, std::process::id()));
+        fs::write(&path, content).unwrap();
+        let theme = Theme::load(&path);
+        fs::remove_file(&path).unwrap();
+        theme
+    }
+
+    #[test]
+    fn theme_files_replace_glyphs_of_their_base() {
+        let theme = load("base", r##"{"base": "ascii", "double_width": true,
+            "contents": {"6": {"glyph": "G", "color": "#00ff00"}, "30": {"glyph": "!", "wide": "!!"}}}"##).unwrap();
+        assert!(theme.ascii_lines && theme.double_width);
+        let g = theme.get_glyph(6);
+        assert_eq!((g.narrow, g.wide, g.color), ('G', ['G', ' '], Some(Color::Rgb(0, 255, 0))));
+        assert_eq!(theme.get_glyph(1).narrow, '.');
+        assert_eq!(theme.get_glyph(30).wide, ['!', '!']);
+        assert_eq!(theme.get_glyph(29).narrow, 'X');
+        assert!(!load("default", "{}").unwrap().ascii_lines);
+    }
+
+    #[test]
+    fn rejects_bad_theme_files() {
+        assert!(load("color", r#"{"contents": {"1": {"color": "mauve"}}}"#).err().unwrap().contains("invalid color 'mauve'"));
+        assert!(load("base", r#"{"base": "sepia"}"#).err().unwrap().contains("unknown theme 'sepia'"));
+        assert!(load("json", r#"{"contents": []"#).is_err());
+        assert!(Theme::load(Path::new("/nonexistent/theme.json")).is_err());
+    }
+}
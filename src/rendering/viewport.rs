@@ -0,0 +1,122 @@
+
+use std::cmp;
+
+/// Squares kept between the followed unit and the view edges
+const FOLLOW_MARGIN: i32 = 3;
+
+/// Part of the board shown on screen, in board coordinates
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl Viewport {
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+    }
+}
+
+/// Moves the viewport with the focused unit, until panned away
+pub struct Camera {
+    x: i32,
+    y: i32,
+    following: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera::new()
+    }
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera{x:0, y:0, following:true}
+    }
+
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.x += dx;
+        self.y += dy;
+        self.following = false;
+    }
+
+    pub fn follow(&mut self) {
+        self.following = true;
+    }
+
+    /// Viewport of at most `view_w` x `view_h` squares over a `board_w` x `board_h` board
+    pub fn update(&mut self, board_w: u32, board_h: u32, view_w: u32, view_h: u32, focus: Option<(u32, u32)>) -> Viewport {
+        let w = cmp::min(board_w, view_w);
+        let h = cmp::min(board_h, view_h);
+        if self.following {
+            if let Some((fx, fy)) = focus {
+                self.x = scroll_to(self.x, fx as i32, w as i32);
+                self.y = scroll_to(self.y, fy as i32, h as i32);
+            }
+        }
+        self.x = cmp::max(0, cmp::min(self.x, (board_w - w) as i32));
+        self.y = cmp::max(0, cmp::min(self.y, (board_h - h) as i32));
+        Viewport{x:self.x as u32, y:self.y as u32, w, h}
+    }
+}
+
+/// Smallest scroll from `start` keeping `target` away from the edges of a `size` wide view
+fn scroll_to(start: i32, target: i32, size: i32) -> i32 {
+    let margin = cmp::min(FOLLOW_MARGIN, (size - 1) / 2);
+    if target < start + margin {
+        target - margin
+    }
+    else if target >= start + size - margin {
+        target - size + margin + 1
+    }
+    else {
+        start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corner(v: Viewport) -> (u32, u32) {
+        (v.x, v.y)
+    }
+
+    #[test]
+    fn follows_the_focus_within_the_board() {
+        let mut camera = Camera::new();
+        assert_eq!(corner(camera.update(30, 20, 10, 10, Some((0, 0)))), (0, 0));
+        // Scrolled just enough to keep the margin
+        assert_eq!(corner(camera.update(30, 20, 10, 10, Some((15, 10)))), (9, 4));
+        assert_eq!(corner(camera.update(30, 20, 10, 10, Some((14, 9)))), (9, 4));
+        // Stopped at the far edges
+        let v = camera.update(30, 20, 10, 10, Some((29, 19)));
+        assert_eq!((v.x, v.y, v.w, v.h), (20, 10, 10, 10));
+        assert!(v.contains(29, 19) && !v.contains(19, 19));
+    }
+
+    #[test]
+    fn panning_stops_following_until_asked() {
+        let mut camera = Camera::new();
+        camera.pan(-5, -5);
+        assert_eq!(corner(camera.update(30, 20, 10, 10, None)), (0, 0));
+        camera.pan(100, 100);
+        assert_eq!(corner(camera.update(30, 20, 10, 10, Some((0, 0)))), (20, 10));
+        camera.follow();
+        assert_eq!(corner(camera.update(30, 20, 10, 10, Some((0, 0)))), (0, 0));
+    }
+
+    #[test]
+    fn board_smaller_than_the_view_is_shown_whole() {
+        let mut camera = Camera::new();
+        camera.pan(3, 3);
+        let v = camera.update(5, 4, 10, 10, Some((4, 3)));
+        assert_eq!((v.x, v.y, v.w, v.h), (0, 0, 5, 4));
+        camera.follow();
+        let v = camera.update(5, 4, 10, 10, Some((4, 3)));
+        assert_eq!((v.x, v.y, v.w, v.h), (0, 0, 5, 4));
+    }
+}
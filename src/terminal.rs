@@ -0,0 +1,50 @@
+
+use crossterm::{
+    cursor::Show,
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io;
+use std::panic;
+
+/// Put the terminal back the way the shell expects it
+pub fn restore() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), LeaveAlternateScreen, Show);
+}
+
+/// Restore the terminal before printing any panic, whichever thread panics
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore();
+        default_hook(info);
+    }));
+}
+
+/// Give the terminal back to the shell while `run` runs, e.g. a pager, then take it again
+pub fn hand_off<T>(run: impl FnOnce() -> T) -> crossterm::Result<T> {
+    restore();
+    let result = run();
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    Ok(result)
+}
+
+/// Raw mode on the alternate screen, restored when dropped
+pub struct Guard {}
+
+impl Guard {
+    pub fn enter() -> crossterm::Result<Guard> {
+        enable_raw_mode()?;
+        let guard = Guard{};
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        restore();
+    }
+}